@@ -0,0 +1,42 @@
+//! Records a port for a while, echoing what arrives, then saves the session
+//! as a CSV event log that the app can open and replay.
+//!
+//! ```sh
+//! cargo run --example capture -- /dev/ttyUSB0 115200 10 capture.csv
+//! ```
+
+use std::{env, fs::File, io::BufWriter, time::Duration};
+
+use serial_monitor::{
+    connection::{Connection, DEFAULT_BR},
+    export::{self, Format},
+};
+use tokio::time::{interval, Instant};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = env::args().skip(1);
+    let port = args.next().unwrap_or_else(|| "/dev/ttyUSB0".to_string());
+    let baud_rate = args.next().map_or(Ok(DEFAULT_BR), |s| s.parse())?;
+    let seconds = args.next().map_or(Ok(10), |s| s.parse())?;
+    let path = args.next().unwrap_or_else(|| "capture.csv".to_string());
+
+    let mut connection = Connection::new(baud_rate);
+    connection.open(&port)?;
+    let end = Instant::now() + Duration::from_secs(seconds);
+    let mut interval = interval(Duration::from_millis(20));
+    while Instant::now() < end {
+        interval.tick().await;
+        print!("{}", connection.read()?);
+    }
+
+    export::write(
+        Format::CsvEvents,
+        connection.records(),
+        &[],
+        &connection.metadata(),
+        BufWriter::new(File::create(&path)?),
+    )?;
+    eprintln!("Saved {} records to {path}", connection.records().len());
+    Ok(())
+}
@@ -0,0 +1,23 @@
+//! Prints the serial ports on this machine.
+//!
+//! ```sh
+//! cargo run --example list_ports
+//! ```
+
+use serial_monitor::ports;
+
+fn main() {
+    for port in ports::get_available_usb() {
+        match port.usb() {
+            Some(usb) => println!(
+                "{}\t{:04x}:{:04x}\t{} {}",
+                port.name(),
+                usb.vid,
+                usb.pid,
+                port.manufacturer(),
+                port.product()
+            ),
+            None => println!("{}", port.name()),
+        }
+    }
+}
@@ -0,0 +1,379 @@
+//! Parsing of ANSI escape sequences into styled lines.
+
+use std::{iter::Peekable, str::Chars};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum AnsiMode {
+    /// Interpret SGR colors and the common cursor/erase sequences.
+    #[default]
+    Render,
+    /// Remove escape sequences and show the plain text.
+    Strip,
+    /// Show the data exactly as received.
+    Raw,
+}
+
+impl AnsiMode {
+    pub const ALL: [AnsiMode; 3] = [AnsiMode::Render, AnsiMode::Strip, AnsiMode::Raw];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnsiMode::Render => "Render",
+            AnsiMode::Strip => "Strip",
+            AnsiMode::Raw => "Raw",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+// xterm's default palette, slightly lifted so that blue stays readable on the
+// dark theme.
+const PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 49, 49),
+    (13, 188, 121),
+    (229, 229, 16),
+    (59, 120, 255),
+    (188, 63, 188),
+    (17, 168, 205),
+    (229, 229, 229),
+    (102, 102, 102),
+    (241, 76, 76),
+    (35, 209, 139),
+    (245, 245, 67),
+    (59, 142, 234),
+    (214, 112, 214),
+    (41, 184, 219),
+    (255, 255, 255),
+];
+
+impl Color {
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        match *self {
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Indexed(i @ 0..=15) => PALETTE[i as usize],
+            Color::Indexed(i @ 16..=231) => {
+                let level = |x: u8| if x == 0 { 0 } else { 55 + x * 40 };
+                let i = i - 16;
+                (level(i / 36), level(i / 6 % 6), level(i % 6))
+            }
+            Color::Indexed(i) => {
+                let v = 8 + (i - 232) * 10;
+                (v, v, v)
+            }
+        }
+    }
+
+    pub fn css(&self) -> String {
+        let (r, g, b) = self.rgb();
+        format!("#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub inverse: bool,
+}
+
+impl Style {
+    pub fn css(&self) -> String {
+        let (fg, bg) = if self.inverse {
+            (
+                self.bg
+                    .map(|c| c.css())
+                    .or(Some("var(--bs-body-bg)".to_string())),
+                self.fg
+                    .map(|c| c.css())
+                    .or(Some("var(--bs-body-color)".to_string())),
+            )
+        } else {
+            (self.fg.map(|c| c.css()), self.bg.map(|c| c.css()))
+        };
+        let mut css = String::new();
+        if let Some(fg) = fg {
+            css.push_str(&format!("color: {fg};"));
+        }
+        if let Some(bg) = bg {
+            css.push_str(&format!("background-color: {bg};"));
+        }
+        if self.bold {
+            css.push_str("font-weight: bold;");
+        }
+        if self.dim {
+            css.push_str("opacity: 0.7;");
+        }
+        if self.italic {
+            css.push_str("font-style: italic;");
+        }
+        if self.underline {
+            css.push_str("text-decoration: underline;");
+        }
+        css
+    }
+
+    fn apply_sgr(&mut self, params: &[u16]) {
+        let mut params = params.iter().copied();
+        while let Some(p) = params.next() {
+            match p {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                30..=37 => self.fg = Some(Color::Indexed((p - 30) as u8)),
+                38 => self.fg = extended_color(&mut params),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(Color::Indexed((p - 40) as u8)),
+                48 => self.bg = extended_color(&mut params),
+                49 => self.bg = None,
+                90..=97 => self.fg = Some(Color::Indexed((p - 90 + 8) as u8)),
+                100..=107 => self.bg = Some(Color::Indexed((p - 100 + 8) as u8)),
+                _ => (),
+            }
+        }
+    }
+}
+
+fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    match params.next()? {
+        5 => Some(Color::Indexed(params.next()? as u8)),
+        2 => Some(Color::Rgb(
+            params.next()? as u8,
+            params.next()? as u8,
+            params.next()? as u8,
+        )),
+        _ => None,
+    }
+}
+
+/// A line of text with the styles it was printed in. `runs` holds the byte
+/// offset at which each style starts, the first one at 0.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Line {
+    pub text: String,
+    pub runs: Vec<(usize, Style)>,
+}
+
+impl Line {
+    fn plain(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            runs: vec![(0, Style::default())],
+        }
+    }
+
+    fn from_cells(cells: &[(char, Style)]) -> Self {
+        let mut line = Line::default();
+        for &(c, style) in cells {
+            if line.runs.last().map(|(_, s)| *s != style).unwrap_or(true) {
+                line.runs.push((line.text.len(), style));
+            }
+            line.text.push(c);
+        }
+        line
+    }
+
+    /// Returns the style of every byte of `text`.
+    pub fn styles(&self) -> Vec<Style> {
+        let mut res = vec![Style::default(); self.text.len()];
+        for (i, (start, style)) in self.runs.iter().enumerate() {
+            let end = self.runs.get(i + 1).map(|r| r.0).unwrap_or(self.text.len());
+            res[*start..end].iter_mut().for_each(|s| *s = *style);
+        }
+        res
+    }
+}
+
+pub fn parse(content: &str, mode: AnsiMode) -> Vec<Line> {
+    parse_with(content, mode, &mut Style::default())
+}
+
+/// Like `parse`, but starts in `style` and leaves it as set at the end, so a
+/// stream can be parsed a line at a time.
+pub fn parse_with(content: &str, mode: AnsiMode, style: &mut Style) -> Vec<Line> {
+    if mode == AnsiMode::Raw {
+        return content.lines().map(Line::plain).collect();
+    }
+    let render = mode == AnsiMode::Render;
+    let mut lines = Vec::new();
+    let mut cells: Vec<(char, Style)> = Vec::new();
+    let mut cursor = 0usize;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                lines.push(Line::from_cells(&cells));
+                cells.clear();
+                cursor = 0;
+            }
+            '\r' if render => cursor = 0,
+            '\x08' if render => cursor = cursor.saturating_sub(1),
+            '\x1b' => match chars.next() {
+                Some('[') => {
+                    let (params, action) = read_csi(&mut chars);
+                    if render {
+                        apply_csi(&params, action, style, &mut cells, &mut cursor);
+                    }
+                }
+                Some(']') => skip_osc(&mut chars),
+                Some('(' | ')') => {
+                    chars.next();
+                }
+                _ => (),
+            },
+            c if c.is_control() && c != '\t' => (),
+            c => {
+                let cell = (c, if render { *style } else { Style::default() });
+                if cursor < cells.len() {
+                    cells[cursor] = cell;
+                } else {
+                    cells.push(cell);
+                }
+                cursor += 1;
+            }
+        }
+    }
+    if !cells.is_empty() {
+        lines.push(Line::from_cells(&cells));
+    }
+    lines
+}
+
+fn read_csi(chars: &mut Peekable<Chars>) -> (Vec<u16>, Option<char>) {
+    let mut raw = String::new();
+    for c in chars.by_ref() {
+        if ('\x40'..='\x7e').contains(&c) {
+            let params = raw
+                .trim_start_matches('?')
+                .split(';')
+                .map(|p| p.parse().unwrap_or(0))
+                .collect();
+            return (params, Some(c));
+        }
+        raw.push(c);
+    }
+    (Vec::new(), None)
+}
+
+fn skip_osc(chars: &mut Peekable<Chars>) {
+    while let Some(c) = chars.next() {
+        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+            return;
+        }
+    }
+}
+
+fn apply_csi(
+    params: &[u16],
+    action: Option<char>,
+    style: &mut Style,
+    cells: &mut Vec<(char, Style)>,
+    cursor: &mut usize,
+) {
+    let n = params.first().copied().unwrap_or(0);
+    let count = n.max(1) as usize;
+    match action {
+        Some('m') => style.apply_sgr(params),
+        Some('K') => match n {
+            0 => cells.truncate(*cursor),
+            1 => cells
+                .iter_mut()
+                .take(*cursor + 1)
+                .for_each(|c| *c = (' ', Style::default())),
+            _ => cells.clear(),
+        },
+        Some('C') => *cursor += count,
+        Some('D') => *cursor = cursor.saturating_sub(count),
+        Some('G') => *cursor = count - 1,
+        _ => return,
+    }
+    while cells.len() < *cursor {
+        cells.push((' ', Style::default()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn red() -> Style {
+        Style {
+            fg: Some(Color::Indexed(1)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sgr_sets_runs() {
+        let lines = parse("a\x1b[31mb\x1b[0mc", AnsiMode::Render);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].text, "abc");
+        assert_eq!(
+            lines[0].runs,
+            [(0, Style::default()), (1, red()), (2, Style::default())]
+        );
+    }
+
+    #[test]
+    fn extended_colors() {
+        let lines = parse("\x1b[38;5;196;48;2;1;2;3mx", AnsiMode::Render);
+        let style = lines[0].runs[0].1;
+        assert_eq!(style.fg, Some(Color::Indexed(196)));
+        assert_eq!(style.bg, Some(Color::Rgb(1, 2, 3)));
+    }
+
+    #[test]
+    fn cursor_movement_overwrites() {
+        let lines = parse("hello\rj\x1b[Kx\nab\x08c", AnsiMode::Render);
+        let texts = lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["jx", "ac"]);
+    }
+
+    #[test]
+    fn strip_and_raw() {
+        let content = "\x1b[1mbold\x1b]0;title\x07\r\n";
+        assert_eq!(parse(content, AnsiMode::Strip)[0].text, "bold");
+        assert_eq!(parse(content, AnsiMode::Strip)[0].runs.len(), 1);
+        assert_eq!(parse(content, AnsiMode::Raw)[0].text, content.trim_end());
+    }
+
+    #[test]
+    fn parse_with_carries_style() {
+        let mut style = Style::default();
+        let first = parse_with("\x1b[31ma\n", AnsiMode::Render, &mut style);
+        let second = parse_with("b\n", AnsiMode::Render, &mut style);
+        assert_eq!(style, red());
+        assert_eq!(
+            [first, second].concat(),
+            parse("\x1b[31ma\nb\n", AnsiMode::Render)
+        );
+    }
+
+    #[test]
+    fn palette() {
+        assert_eq!(Color::Indexed(16).rgb(), (0, 0, 0));
+        assert_eq!(Color::Indexed(231).rgb(), (255, 255, 255));
+        assert_eq!(Color::Indexed(232).rgb(), (8, 8, 8));
+        assert_eq!(Color::Rgb(1, 2, 3).css(), "#010203");
+    }
+}
@@ -1,22 +1,40 @@
 use std::{
-    io::{Error, ErrorKind::NotConnected},
-    path::PathBuf,
+    io::{self, BufWriter},
+    ops::Range,
+    path::{Path, PathBuf},
 };
 
 use dioxus::prelude::*;
 use dirs::download_dir;
+use futures::StreamExt;
 use log::*;
 use rfd::{AsyncFileDialog, AsyncMessageDialog, MessageButtons, MessageLevel};
-use tokio::time::{interval, Duration};
+use serde_json::json;
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast, mpsc, oneshot},
+    time::{interval, sleep_until, Duration, Instant},
+};
 
-use crate::{
-    handle::{self, Handle},
+use serial_monitor::{
+    connection::Connection,
+    export::{self, Format, Metadata},
+    highlight::{HighlightRule, Highlighter},
+    import::{self, Capture},
+    plot::Plot,
     ports::{self, PortInfo},
+    record::{Direction, Record},
+    recorder::{self, Recorder, RecordingSettings},
 };
 
-pub const SCAN_FREQ: Duration = Duration::from_millis(500);
-pub const READ_FREQ: Duration = Duration::from_millis(20);
-pub const DEFAULT_BR: u32 = 9600;
+use crate::{
+    bridge::{self, BridgeSettings, BridgeStatus},
+    control::{Call, Method, Response},
+    dashboard::Dashboard,
+    responder::{self, Reply, Responder},
+    sniffer::{self, Sniffer, SnifferStatus},
+    READ_FREQ, SCAN_FREQ,
+};
 
 pub async fn scan_ports(buffer: UseState<Vec<PortInfo>>) {
     let mut interval = interval(SCAN_FREQ);
@@ -26,20 +44,51 @@ pub async fn scan_ports(buffer: UseState<Vec<PortInfo>>) {
     }
 }
 
-pub async fn read(connection: UseRef<Connection>, buffer: UseRef<Vec<String>>) {
+pub async fn read(
+    connection: UseRef<Connection>,
+    buffer: UseRef<Vec<String>>,
+    user_buffer: UseRef<Vec<String>>,
+    responder: UseRef<Responder>,
+) {
     let mut interval = interval(READ_FREQ);
-    info!("Reading from {:?}", connection.read().handle);
+    let mut replies: Vec<(Instant, Reply)> = Vec::new();
+    info!("Reading from {}", connection.read().get_name());
     while connection.with(|c| c.has_handle()) {
         interval.tick().await;
-        let data = connection.write().read();
-        match data {
-            Ok(x) => {
-                if !x.is_empty() {
+        // Take every frame that arrived since the last tick, so that record
+        // subscribers such as the bridges don't fall behind a busy port.
+        loop {
+            let data = connection.write().read();
+            match data {
+                Ok(x) if x.is_empty() => break,
+                Ok(x) => {
+                    let reply = responder.write_silent().respond(&x);
                     buffer.with_mut(|b| b.push(x));
+                    if let Some(reply) = &reply {
+                        mark(
+                            &buffer,
+                            &format!("auto-reply: {}", reply.rule),
+                            &responder::escape(&reply.data),
+                        );
+                    }
+                    if let Some(reply) = reply {
+                        responder.needs_update();
+                        replies.push((Instant::now() + reply.delay, reply));
+                    }
+                }
+                Err(e) => {
+                    debug!("{:?}", e);
+                    break;
                 }
             }
-            Err(e) => {
-                debug!("{:?}", e);
+        }
+        let now = Instant::now();
+        while let Some(i) = replies.iter().position(|(due, _)| *due <= now) {
+            let (_, reply) = replies.remove(i);
+            if let Err(e) = connection.write().write_bytes(&reply.data) {
+                error!("Auto-reply {} failed: {:?}", reply.rule, e);
+            } else {
+                user_buffer.with_mut(|b| b.push(String::from_utf8_lossy(&reply.data).into_owned()));
             }
         }
     }
@@ -49,92 +98,494 @@ pub async fn read(connection: UseRef<Connection>, buffer: UseRef<Vec<String>>) {
     );
 }
 
-pub async fn connect(connection: UseRef<Connection>, port: &str) {
-    let mut interval = interval(SCAN_FREQ);
+pub async fn plot(connection: UseRef<Connection>, plot: UseRef<Plot>) {
+    let mut rx = connection.write_silent().subscribe();
+    while let Some(data) = rx.recv().await {
+        plot.write_silent().push(&data);
+    }
+}
+
+pub async fn dashboard(
+    connection: UseRef<Connection>,
+    dashboard: UseRef<Dashboard>,
+    buffer: UseRef<Vec<String>>,
+) {
+    let mut rx = connection.write_silent().subscribe();
+    while let Some(data) = rx.recv().await {
+        let events = dashboard.write_silent().push(&data);
+        for e in events {
+            mark(&buffer, "alarm", &e.describe());
+        }
+    }
+}
+
+/// Feeds every record to the active recording, if any. A recording that
+/// fails to write is stopped and the error reported.
+pub async fn record(connection: UseRef<Connection>, recorder: UseRef<Option<Recorder>>) {
+    let mut rx = connection.write_silent().subscribe_records();
+    let mut interval = interval(recorder::FLUSH_FREQ);
     loop {
-        interval.tick().await;
-        match connection.write().open(port) {
-            Err(e) => {
-                error!("{:?}", e);
-            }
-            Ok(_) => break,
+        let res = tokio::select! {
+            record = rx.recv() => match record {
+                Some(record) => recorder.write_silent().as_mut().map(|r| r.write(&record)),
+                None => break,
+            },
+            _ = interval.tick() => recorder.write_silent().as_mut().map(|r| r.tick()),
+        };
+        if let Some(Err(e)) = res {
+            error!("Recording failed: {:?}", e);
+            recorder.set(None);
+            show_error_dialog("Recording stopped", &format!("{e}")).await;
         }
     }
 }
 
-#[derive(Debug)]
-pub struct Connection {
-    handle: Option<Handle>,
-    name: Option<String>,
-    baud_rate: u32,
+/// The views fed by received data, for showing data that did not come from
+/// the port.
+#[derive(Clone, PartialEq)]
+pub struct Viewer {
+    pub port_buffer: UseRef<Vec<String>>,
+    pub user_buffer: UseRef<Vec<String>>,
+    pub plot: UseRef<Plot>,
+    pub dashboard: UseRef<Dashboard>,
 }
 
-impl Connection {
-    pub fn new(baud_rate: u32) -> Self {
-        Self {
-            handle: None,
-            name: None,
-            baud_rate,
-        }
+impl Viewer {
+    /// Shows `record` as if it had just been sent or received.
+    fn push(&self, record: &Record) {
+        self.push_at(record, None);
     }
 
-    #[must_use]
-    pub fn open(&mut self, port: &str) -> handle::Result<()> {
-        self.handle = Some(Handle::open(port, self.baud_rate)?);
-        self.name = Some(port.to_string());
-        Ok(())
+    /// `plot_time` places received values on the plot's time axis instead of
+    /// at the current time.
+    fn push_at(&self, record: &Record, plot_time: Option<f64>) {
+        let text = record.text().into_owned();
+        if record.direction == Direction::Tx {
+            self.user_buffer.with_mut(|b| b.push(text));
+            return;
+        }
+        match plot_time {
+            Some(t) => self.plot.write_silent().push_at(t, &text),
+            None => self.plot.write_silent().push(&text),
+        }
+        let events = match plot_time {
+            Some(_) => self
+                .dashboard
+                .write_silent()
+                .push_at(record.unix_time(), &text),
+            None => self.dashboard.write_silent().push(&text),
+        };
+        self.port_buffer.with_mut(|b| b.push(text));
+        for e in events {
+            mark(&self.port_buffer, "alarm", &e.describe());
+        }
     }
 
-    pub fn close(&mut self) {
-        self.handle = None;
-        self.name = None;
+    /// Replaces whatever is shown with a whole capture at once.
+    pub fn show(&self, capture: &Capture) {
+        self.port_buffer.with_mut(|b| b.clear());
+        self.user_buffer.with_mut(|b| b.clear());
+        self.plot.with_mut(|p| p.reset_to(capture.duration()));
+        self.dashboard.with_mut(|d| d.clear());
+        if let Some(first) = capture.records.first() {
+            for r in &capture.records {
+                self.push_at(r, Some(import::seconds_between(first, r)));
+            }
+        }
     }
+}
 
-    #[allow(dead_code)]
-    pub fn get_baud_rate(&self) -> u32 {
-        self.baud_rate
+/// An exported log opened for viewing or replay.
+pub struct LoadedLog {
+    pub path: PathBuf,
+    pub capture: Capture,
+    /// Records replayed so far.
+    pub position: usize,
+    pub task: Option<TaskId>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReplayTarget {
+    /// Into the consoles, plot and dashboard.
+    View,
+    /// Received records go out through the connection, to play the device
+    /// side of a capture to whatever is on the bench.
+    Port,
+}
+
+/// Plays the loaded log back with its original timing, scaled by `speed`.
+pub async fn replay(
+    log: UseRef<Option<LoadedLog>>,
+    target: ReplayTarget,
+    speed: f64,
+    connection: UseRef<Connection>,
+    viewer: Viewer,
+) {
+    let Some(records) = log.read().as_ref().map(|l| l.capture.records.clone()) else {
+        return;
+    };
+    let origin = Instant::now();
+    let mut refreshed = origin;
+    for (i, r) in records.iter().enumerate() {
+        let offset = import::seconds_between(&records[0], r) / speed;
+        sleep_until(origin + Duration::from_secs_f64(offset)).await;
+        match target {
+            ReplayTarget::View => viewer.push(r),
+            ReplayTarget::Port if r.direction == Direction::Rx => {
+                let res = connection.write().write_bytes(&r.data);
+                if let Err(e) = res {
+                    error!("Replay stopped: {:?}", e);
+                    show_error_dialog("Replay stopped", &format!("{e}")).await;
+                    break;
+                }
+            }
+            ReplayTarget::Port => (),
+        }
+        if let Some(l) = log.write_silent().as_mut() {
+            l.position = i + 1;
+        }
+        if refreshed.elapsed() >= SCAN_FREQ {
+            refreshed = Instant::now();
+            log.needs_update();
+        }
     }
+    log.with_mut(|l| {
+        if let Some(l) = l {
+            l.task = None;
+        }
+    });
+}
+
+/// Inserts an annotation on a line of its own into a console buffer.
+pub fn mark(buffer: &UseRef<Vec<String>>, tag: &str, text: &str) {
+    buffer.with_mut(|b| {
+        let sep = match b.last() {
+            Some(last) if !last.ends_with('\n') => "\n",
+            _ => "",
+        };
+        b.push(format!("{sep}[{tag}] {text}\n"));
+    });
+}
 
-    pub fn get_name(&self) -> &str {
-        self.name.as_ref().map(|s| s.as_str()).unwrap_or("none")
+/// Owns the connection's lifecycle. Each port name received (or `None` to
+/// disconnect) replaces the current port in `selected`; it is opened as
+/// soon as it appears and then read until the next request.
+pub async fn session(
+    mut requests: UnboundedReceiver<Option<String>>,
+    connection: UseRef<Connection>,
+    selected: UseRef<Option<String>>,
+    buffer: UseRef<Vec<String>>,
+    user_buffer: UseRef<Vec<String>>,
+    responder: UseRef<Responder>,
+) {
+    let mut request = requests.next().await;
+    while let Some(port) = request {
+        connection.with_mut(|c| c.close());
+        selected.set(port.clone());
+        let Some(port) = port else {
+            request = requests.next().await;
+            continue;
+        };
+        let run = async {
+            connect(connection.clone(), &port).await;
+            info!("Connected to {}", port);
+            read(
+                connection.clone(),
+                buffer.clone(),
+                user_buffer.clone(),
+                responder.clone(),
+            )
+            .await;
+        };
+        request = tokio::select! {
+            _ = run => requests.next().await,
+            next = requests.next() => next,
+        };
     }
+}
 
-    #[must_use]
-    pub fn set_baud_rate(&mut self, rate: u32) -> handle::Result<()> {
-        if self.handle.is_none() {
-            self.baud_rate = rate;
-            return Ok(());
+pub async fn connect(connection: UseRef<Connection>, port: &str) {
+    let mut interval = interval(SCAN_FREQ);
+    loop {
+        interval.tick().await;
+        match connection.write().open(port) {
+            Err(e) => {
+                error!("{:?}", e);
+            }
+            Ok(_) => break,
         }
-        self.baud_rate = rate;
-        debug!("{}", self.baud_rate);
-        let name = self.name.as_ref().unwrap();
-        self.handle.as_mut().unwrap().reconnect(name, rate)?;
-        Ok(())
     }
+}
+
+/// How long a control client's `connect` waits for the port to open.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
 
-    pub fn is_connected(&self) -> bool {
-        self.handle
-            .as_ref()
-            .map(|h| h.is_connected())
-            .unwrap_or(false)
+/// Carries out requests from the control server on the app's own state, so
+/// that they show up in the window like the user's own actions.
+pub async fn control(
+    mut calls: mpsc::UnboundedReceiver<Call>,
+    session: Coroutine<Option<String>>,
+    connection: UseRef<Connection>,
+    recording: UseRef<RecordingSettings>,
+    recorder: UseRef<Option<Recorder>>,
+    highlighter: UseRef<Highlighter>,
+    user_buffer: UseRef<Vec<String>>,
+) {
+    // Connect calls are answered once the session has switched ports.
+    let mut pending: Vec<(Option<String>, Instant, oneshot::Sender<_>)> = Vec::new();
+    let mut check = interval(READ_FREQ);
+    loop {
+        let Call { method, reply } = tokio::select! {
+            call = calls.recv() => match call {
+                Some(call) => call,
+                None => return,
+            },
+            _ = check.tick(), if !pending.is_empty() => {
+                for (port, since, reply) in std::mem::take(&mut pending) {
+                    let done = {
+                        let c = connection.read();
+                        match &port {
+                            Some(port) => c.is_connected() && c.get_name() == port,
+                            None => !c.has_handle(),
+                        }
+                    };
+                    if done {
+                        let _ = reply.send(Ok(Response::Value(json!(true))));
+                    } else if since.elapsed() >= CONNECT_TIMEOUT {
+                        let _ = reply.send(Err(
+                            "The port did not open in time; still retrying, see status".to_string(),
+                        ));
+                    } else {
+                        pending.push((port, since, reply));
+                    }
+                }
+                continue;
+            }
+        };
+        debug!("Control request {:?}", method);
+        let res = match method {
+            Method::ListPorts => {
+                let ports: Vec<_> = ports::get_available_usb()
+                    .iter()
+                    .map(|p| {
+                        json!({
+                            "name": p.name(),
+                            "manufacturer": p.manufacturer(),
+                            "product": p.product(),
+                            "vid": p.usb().map(|u| u.vid),
+                            "pid": p.usb().map(|u| u.pid),
+                            "serial_number": p.usb().and_then(|u| u.serial_number.clone()),
+                        })
+                    })
+                    .collect();
+                Ok(Response::Value(json!(ports)))
+            }
+            Method::Status => {
+                let c = connection.read();
+                Ok(Response::Value(json!({
+                    "port": c.has_handle().then(|| c.get_name()),
+                    "connected": c.is_connected(),
+                    "baud": c.get_baud_rate(),
+                    "recording": recorder.read().as_ref().and_then(|r| r.path()),
+                })))
+            }
+            Method::Connect(port) => {
+                // The session keeps retrying until the port shows up.
+                session.send(port.clone());
+                pending.push((port, Instant::now(), reply));
+                continue;
+            }
+            Method::Send(data) => match connection.write().write(&data) {
+                Ok(_) => {
+                    user_buffer.with_mut(|b| b.push(data));
+                    Ok(Response::Value(json!(true)))
+                }
+                Err(e) => Err(e.to_string()),
+            },
+            Method::SetBaud(baud) => match connection.with_mut(|c| c.set_baud_rate(baud)) {
+                Ok(_) => {
+                    info!("Baud rate set to {baud}");
+                    Ok(Response::Value(json!(true)))
+                }
+                Err(e) => Err(e.to_string()),
+            },
+            Method::StartRecording => {
+                let started = if recorder.read().is_some() {
+                    Ok(())
+                } else {
+                    let settings = recording.read().clone();
+                    let rules = highlighter.read().rules().to_vec();
+                    Recorder::start(settings, rules, connection.read().metadata())
+                        .map(|r| recorder.set(Some(r)))
+                };
+                match started {
+                    Ok(_) => {
+                        let path = recorder
+                            .read()
+                            .as_ref()
+                            .and_then(|r| r.path().map(|p| p.to_owned()));
+                        Ok(Response::Value(json!({ "path": path })))
+                    }
+                    Err(e) => Err(format!("Failed to start recording: {e}")),
+                }
+            }
+            Method::StopRecording => {
+                recorder.set(None);
+                Ok(Response::Value(json!(true)))
+            }
+            Method::Subscribe => Ok(Response::Records(connection.write().subscribe_records())),
+        };
+        let _ = reply.send(res);
     }
+}
 
-    pub fn has_handle(&self) -> bool {
-        self.handle.is_some()
+/// Shares the port over TCP, raw, as telnet or over WebSocket, with the
+/// settings received until `None` or new settings arrive.
+pub async fn bridge(
+    mut requests: UnboundedReceiver<Option<BridgeSettings>>,
+    connection: UseRef<Connection>,
+    status: UseRef<BridgeStatus>,
+    user_buffer: UseRef<Vec<String>>,
+) {
+    let mut request = requests.next().await;
+    while let Some(settings) = request {
+        status.set(BridgeStatus::default());
+        let Some(settings) = settings else {
+            request = requests.next().await;
+            continue;
+        };
+        let run = run_bridge(&settings, &connection, &status, &user_buffer);
+        request = tokio::select! {
+            res = run => {
+                if let Err(e) = res {
+                    error!("Bridge failed: {:?}", e);
+                    status.set(BridgeStatus {
+                        error: Some(e.to_string()),
+                        ..Default::default()
+                    });
+                }
+                requests.next().await
+            }
+            next = requests.next() => next,
+        };
     }
+}
 
-    pub fn write(&mut self, data: &str) -> handle::Result<()> {
-        self.handle
-            .as_ref()
-            .ok_or(Error::new(NotConnected, "Not connected"))
-            .and_then(|h| h.write(data))
+async fn run_bridge(
+    settings: &BridgeSettings,
+    connection: &UseRef<Connection>,
+    status: &UseRef<BridgeStatus>,
+    user_buffer: &UseRef<Vec<String>>,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind(&settings.address).await?;
+    let addr = listener.local_addr()?;
+    info!("Bridging to {addr} ({:?})", settings.protocol);
+    status.with_mut(|s| s.listening = Some(addr));
+    let (data, _) = broadcast::channel(bridge::BACKLOG);
+    let (events_tx, mut events) = mpsc::unbounded_channel();
+    let mut records = connection.write().subscribe_records();
+    let serve = bridge::serve(listener, settings.protocol, data.clone(), events_tx);
+    tokio::pin!(serve);
+    loop {
+        tokio::select! {
+            res = &mut serve => return res,
+            Some(record) = records.recv() => {
+                // Fails only while no client is connected.
+                let _ = data.send(record);
+            }
+            Some(event) = events.recv() => match event {
+                bridge::Event::Joined(id, addr) => status.with_mut(|s| s.clients.push((id, addr))),
+                bridge::Event::Left(id) => status.with_mut(|s| s.clients.retain(|c| c.0 != id)),
+                bridge::Event::Data(id, bytes) => {
+                    if !status.read().may_write(settings, id) {
+                        debug!("Dropped {} bytes from read-only bridge client {id}", bytes.len());
+                    } else {
+                        match connection.write().write_bytes(&bytes) {
+                            Ok(_) => user_buffer
+                                .with_mut(|b| b.push(String::from_utf8_lossy(&bytes).into_owned())),
+                            Err(e) => warn!("Failed to write bridged data: {:?}", e),
+                        }
+                    }
+                }
+                bridge::Event::Break(id) => {
+                    if !status.read().may_write(settings, id) {
+                        debug!("Ignored break from read-only bridge client {id}");
+                    } else if let Err(e) = connection.write().send_break() {
+                        warn!("Failed to send break: {:?}", e);
+                    }
+                }
+            },
+        }
     }
+}
 
-    pub fn read(&mut self) -> handle::Result<String> {
-        self.handle
-            .as_mut()
-            .ok_or(Error::new(NotConnected, "Not connected"))
-            .and_then(|h| h.read())
+/// Runs the two-port sniffer as requested, showing both directions in
+/// `buffer`.
+pub async fn sniff(
+    mut requests: UnboundedReceiver<sniffer::Request>,
+    rules: UseRef<sniffer::Rules>,
+    buffer: UseRef<Vec<String>>,
+    status: UseRef<SnifferStatus>,
+) {
+    let mut sniffer: Option<Sniffer> = None;
+    let (forwarded_tx, mut forwarded) = mpsc::unbounded_channel();
+    let mut check_interval = interval(SCAN_FREQ);
+    loop {
+        tokio::select! {
+            request = requests.next() => match request {
+                Some(sniffer::Request::Start { a, b, baud_rate }) => {
+                    // Release the ports first in case the same ones are reopened.
+                    sniffer = None;
+                    let rules = rules.read().clone();
+                    match Sniffer::open(&a, &b, baud_rate, rules, forwarded_tx.clone()) {
+                        Ok(s) => {
+                            info!("Sniffing between {a} and {b}");
+                            mark(&buffer, "sniffer", &format!("A = {a}, B = {b}, {baud_rate} baud"));
+                            sniffer = Some(s);
+                            status.set(SnifferStatus { running: true, error: None });
+                        }
+                        Err(e) => {
+                            error!("Failed to start sniffer: {:?}", e);
+                            status.set(SnifferStatus { running: false, error: Some(e.to_string()) });
+                        }
+                    }
+                }
+                Some(sniffer::Request::Stop) => {
+                    sniffer = None;
+                    status.set(SnifferStatus::default());
+                }
+                Some(sniffer::Request::Inject(side, text)) => {
+                    let Some(s) = &sniffer else {
+                        continue;
+                    };
+                    let data = responder::unescape(&text);
+                    match s.write(side, &data) {
+                        Ok(_) => mark(
+                            &buffer,
+                            &format!("inject → {}", side.name()),
+                            &responder::escape(&data),
+                        ),
+                        Err(e) => warn!("Failed to inject into {}: {:?}", s.name(side), e),
+                    }
+                }
+                None => return,
+            },
+            Some(f) = forwarded.recv() => {
+                let mut lines = vec![f.lines()];
+                while let Ok(f) = forwarded.try_recv() {
+                    lines.push(f.lines());
+                }
+                buffer.with_mut(|b| b.extend(lines));
+            }
+            _ = check_interval.tick(), if sniffer.is_some() => {
+                let lost = sniffer.as_ref().and_then(|s| s.lost()).map(|p| format!("Lost {p}"));
+                if let Some(e) = lost {
+                    error!("Sniffer stopped: {e}");
+                    sniffer = None;
+                    status.set(SnifferStatus { running: false, error: Some(e) });
+                }
+            }
+        }
     }
 }
 
@@ -154,8 +605,10 @@ fn process_data(titles: Vec<String>, content: Vec<Vec<String>>) -> Vec<Vec<Strin
     res
 }
 
-fn download_csv(data: Vec<Vec<String>>, path: PathBuf) -> csv::Result<()> {
-    let mut wtr = csv::WriterBuilder::new().from_path(&path)?;
+fn download_csv(data: Vec<Vec<String>>, metadata: Metadata, path: PathBuf) -> csv::Result<()> {
+    let mut file = BufWriter::new(std::fs::File::create(&path)?);
+    metadata.write(Format::CsvColumns, &mut file)?;
+    let mut wtr = csv::WriterBuilder::new().from_writer(file);
     data.into_iter().map(|v| wtr.write_record(&v)).collect()
 }
 
@@ -168,58 +621,159 @@ fn start_process_data(
 
 fn start_download_csv(
     data: Vec<Vec<String>>,
+    metadata: Metadata,
     path: PathBuf,
-) -> std::thread::JoinHandle<csv::Result<()>> {
-    std::thread::spawn(move || download_csv(data, path))
+) -> std::thread::JoinHandle<std::io::Result<()>> {
+    std::thread::spawn(move || download_csv(data, metadata, path).map_err(Into::into))
+}
+
+/// How many records a download writes at a time before letting the app
+/// carry on.
+const DOWNLOAD_BATCH: usize = 1000;
+
+/// Writes the logged records in `range`, a few at a time under the lock
+/// so that the log is neither copied nor held for long. Positions are
+/// counted from the start of the session, see `Connection::log_offset`, so
+/// records dropped meanwhile are skipped.
+async fn download_records(
+    format: Format,
+    connection: &UseRef<Connection>,
+    range: Range<usize>,
+    rules: &[HighlightRule],
+    metadata: &Metadata,
+    path: &Path,
+) -> io::Result<()> {
+    let file = std::fs::File::create(path)?;
+    let mut writer = export::RecordWriter::new(format, rules, metadata, BufWriter::new(file))?;
+    let mut next = range.start;
+    while next < range.end {
+        next = connection.with(|c| {
+            let from = next.max(c.log_offset());
+            // Clearing or trimming the log may have dropped the rest.
+            if from >= range.end {
+                return Ok(range.end);
+            }
+            let records = c.records().get(from - c.log_offset()..).unwrap_or_default();
+            let n = records.len().min(DOWNLOAD_BATCH).min(range.end - from);
+            if n == 0 {
+                return Ok(range.end);
+            }
+            records[..n].iter().try_for_each(|r| writer.write(r))?;
+            Ok::<_, io::Error>(from + n)
+        })?;
+        tokio::task::yield_now().await;
+    }
+    writer.finish(None).map(|_| ())
 }
 
-pub async fn download(titles: Vec<String>, content: Vec<Vec<String>>) {
-    let handle = start_process_data(titles, content);
+/// Saves the session. `format` is preselected in the save dialog, but the
+/// extension picked there decides. The connection's records feed every
+/// format except the column layout, which is built from `titles` and the
+/// console buffers, copied only for that layout. Every format but raw bytes starts with `metadata`,
+/// completed from the records.
+pub async fn download(
+    format: Format,
+    connection: UseRef<Connection>,
+    rules: Vec<HighlightRule>,
+    metadata: Metadata,
+    titles: Vec<String>,
+    buffers: [UseRef<Vec<String>>; 2],
+) {
+    let Some(path) = get_record_path(format).await else {
+        return;
+    };
+    let format = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format.for_extension(extension),
+        None => format,
+    };
+    let (metadata, range) = connection.with(|c| {
+        let start = c.log_offset();
+        let metadata = metadata.with_records(c.records());
+        (metadata, start..start + c.records().len())
+    });
     let mut check_interval = interval(SCAN_FREQ);
-    while !handle.is_finished() {
-        check_interval.tick().await;
-    }
-    let data = handle.join().unwrap();
-    match get_download_path().await {
-        None => (),
-        Some(path) => {
-            let handle = start_download_csv(data, path.clone());
+    let res = match format {
+        Format::CsvColumns => {
+            let content = buffers.iter().map(|b| b.read().clone()).collect();
+            let handle = start_process_data(titles, content);
             while !handle.is_finished() {
                 check_interval.tick().await;
             }
-            let res = handle.join().unwrap();
-            match res {
-                Ok(_) => {
-                    info!("Download successful");
-                }
-                Err(e) => {
-                    show_download_error_dialog(format!("{:?}", e).as_str()).await;
-                    if tokio::fs::remove_file(path).await.is_err() {
-                        warn!("Failed to remove download");
-                    } else {
-                        warn!("Download removed due to error downloading");
-                    }
-                }
+            let handle = start_download_csv(handle.join().unwrap(), metadata, path.clone());
+            while !handle.is_finished() {
+                check_interval.tick().await;
             }
+            handle.join().unwrap()
         }
+        _ => download_records(format, &connection, range, &rules, &metadata, &path).await,
+    };
+    match res {
+        Ok(_) => {
+            info!("Download successful");
+        }
+        Err(e) => {
+            show_download_error_dialog(format!("{:?}", e).as_str()).await;
+            if tokio::fs::remove_file(path).await.is_err() {
+                warn!("Failed to remove download");
+            } else {
+                warn!("Download removed due to error downloading");
+            }
+        }
+    }
+}
+
+/// Asks where to save an exported chart or table and writes `contents` there.
+pub async fn export(
+    name: &'static str,
+    extension: &'static str,
+    file_name: &'static str,
+    contents: String,
+) {
+    let Some(path) = get_download_path(name, extension, file_name).await else {
+        return;
+    };
+    match tokio::fs::write(&path, contents).await {
+        Ok(_) => info!("Exported {}", path.display()),
+        Err(e) => show_download_error_dialog(format!("{:?}", e).as_str()).await,
+    }
+}
+
+async fn get_record_path(format: Format) -> Option<PathBuf> {
+    let dir = download_dir()?;
+    let mut dialog = AsyncFileDialog::new().add_filter(format.name(), &[format.extension()]);
+    for f in Format::ALL
+        .iter()
+        .filter(|f| f.extension() != format.extension())
+    {
+        dialog = dialog.add_filter(f.name(), &[f.extension()]);
     }
+    dialog
+        .set_directory(&dir)
+        .set_file_name(format!("record.{}", format.extension()))
+        .save_file()
+        .await
+        .map(|p| p.path().to_owned())
 }
 
-async fn get_download_path() -> Option<PathBuf> {
+async fn get_download_path(name: &str, extension: &str, file_name: &str) -> Option<PathBuf> {
     let dir = download_dir()?;
     AsyncFileDialog::new()
-        .add_filter("CSV", &["csv"])
+        .add_filter(name, &[extension])
         .set_directory(&dir)
-        .set_file_name("record.csv")
+        .set_file_name(file_name)
         .save_file()
         .await
         .map(|p| p.path().to_owned())
 }
 
 async fn show_download_error_dialog(msg: &str) {
+    show_error_dialog("Download failed", msg).await;
+}
+
+pub async fn show_error_dialog(title: &str, msg: &str) {
     AsyncMessageDialog::new()
         .set_level(MessageLevel::Error)
-        .set_title("Download failed")
+        .set_title(title)
         .set_description(msg)
         .set_buttons(MessageButtons::Ok)
         .show()
@@ -1,23 +1,150 @@
 use dioxus::prelude::*;
+use log::*;
 
-use crate::{
-    api::{self, Connection, DEFAULT_BR},
-    components::{consoles::Consoles, input_box::InputBox, selector_row::SelectorRow},
+use serial_monitor::{
+    ansi::AnsiMode,
+    connection::{Connection, DEFAULT_BR},
+    plot::Plot,
     ports::PortInfo,
+    recorder::Recorder,
+};
+
+use crate::{
+    api::{self, LoadedLog, Viewer},
+    bridge::{BridgeSettings, BridgeStatus},
+    components::{
+        consoles::{AnsiModeSelector, Consoles},
+        dashboard_panel::DashboardPanel,
+        file_viewer::FileViewer,
+        highlight_panel::HighlightPanel,
+        input_box::InputBox,
+        log_panel::LogPanel,
+        network_panel::NetworkPanel,
+        panels::{Panel, PanelSelector},
+        plot_panel::PlotPanel,
+        recording_panel::RecordingPanel,
+        responder_panel::ResponderPanel,
+        script_panel::ScriptPanel,
+        selector_row::SelectorRow,
+        sniffer_view::SnifferView,
+        terminal::{SessionMode, SessionModeSelector, Terminal},
+    },
+    control::{self, Endpoint},
+    dashboard::Dashboard,
+    responder::Responder,
+    settings,
+    sniffer::{self, Rules, SnifferStatus},
 };
 
-pub fn App(cx: Scope) -> Element {
+#[derive(Props, PartialEq)]
+pub struct AppProps {
+    /// Where to serve the JSON-RPC control API, if anywhere.
+    pub control: Option<Endpoint>,
+}
+
+pub fn App(cx: Scope<AppProps>) -> Element {
     let available_ports = use_state(cx, || Vec::<PortInfo>::new());
     let connection = use_ref(cx, || Connection::new(DEFAULT_BR));
     let user_buffer = use_ref(cx, || Vec::<String>::new());
     let port_buffer = use_ref(cx, || Vec::<String>::new());
+    let responder = use_ref(cx, Responder::default);
+    let highlighter = use_ref(cx, settings::load_highlighter);
+    let plot = use_ref(cx, Plot::default);
+    let dashboard = use_ref(cx, Dashboard::default);
+    let recording = use_ref(cx, settings::load_recording);
+    let recorder = use_ref(cx, || None::<Recorder>);
+    let log = use_ref(cx, || None::<LoadedLog>);
+    let panel = use_state(cx, || None::<Panel>);
+    let ansi_mode = use_state(cx, AnsiMode::default);
+    let session_mode = use_state(cx, || SessionMode::Console);
+
+    let port = use_ref(cx, || None::<String>);
+    let bridge_settings = use_ref(cx, BridgeSettings::raw);
+    let bridge_status = use_ref(cx, BridgeStatus::default);
+    let telnet_settings = use_ref(cx, BridgeSettings::telnet);
+    let telnet_status = use_ref(cx, BridgeStatus::default);
+    let websocket_settings = use_ref(cx, BridgeSettings::websocket);
+    let websocket_status = use_ref(cx, BridgeStatus::default);
+    let sniff_rules = use_ref(cx, Rules::default);
+    let sniff_buffer = use_ref(cx, || Vec::<String>::new());
+    let sniff_status = use_ref(cx, SnifferStatus::default);
 
+    let session = use_coroutine(cx, |rx: UnboundedReceiver<Option<String>>| {
+        to_owned![connection, port, port_buffer, user_buffer, responder];
+        api::session(rx, connection, port, port_buffer, user_buffer, responder)
+    });
+    let bridge = use_coroutine(cx, |rx: UnboundedReceiver<Option<BridgeSettings>>| {
+        to_owned![connection, bridge_status, user_buffer];
+        api::bridge(rx, connection, bridge_status, user_buffer)
+    });
+    let telnet = use_coroutine(cx, |rx: UnboundedReceiver<Option<BridgeSettings>>| {
+        to_owned![connection, telnet_status, user_buffer];
+        api::bridge(rx, connection, telnet_status, user_buffer)
+    });
+    let websocket = use_coroutine(cx, |rx: UnboundedReceiver<Option<BridgeSettings>>| {
+        to_owned![connection, websocket_status, user_buffer];
+        api::bridge(rx, connection, websocket_status, user_buffer)
+    });
+    let sniffer = use_coroutine(cx, |rx: UnboundedReceiver<sniffer::Request>| {
+        to_owned![sniff_rules, sniff_buffer, sniff_status];
+        api::sniff(rx, sniff_rules, sniff_buffer, sniff_status)
+    });
+    let _ = use_coroutine(cx, |_: UnboundedReceiver<()>| {
+        let endpoint = cx.props.control.clone();
+        to_owned![
+            session,
+            connection,
+            recording,
+            recorder,
+            highlighter,
+            user_buffer
+        ];
+        async move {
+            let Some(endpoint) = endpoint else {
+                return;
+            };
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            tokio::spawn(async move {
+                if let Err(e) = control::serve(endpoint, tx).await {
+                    error!("Control server failed: {:?}", e);
+                }
+            });
+            api::control(
+                rx,
+                session,
+                connection,
+                recording,
+                recorder,
+                highlighter,
+                user_buffer,
+            )
+            .await;
+        }
+    });
     let _ = use_coroutine(cx, |_: UnboundedReceiver<()>| {
         to_owned!(available_ports);
         async move {
             api::scan_ports(available_ports.clone()).await;
         }
     });
+    let _ = use_coroutine(cx, |_: UnboundedReceiver<()>| {
+        to_owned![connection, plot];
+        async move {
+            api::plot(connection, plot).await;
+        }
+    });
+    let _ = use_coroutine(cx, |_: UnboundedReceiver<()>| {
+        to_owned![connection, dashboard, port_buffer];
+        async move {
+            api::dashboard(connection, dashboard, port_buffer).await;
+        }
+    });
+    let _ = use_coroutine(cx, |_: UnboundedReceiver<()>| {
+        to_owned![connection, recorder];
+        async move {
+            api::record(connection, recorder).await;
+        }
+    });
     render! {
         head {
             link {
@@ -38,15 +165,144 @@ pub fn App(cx: Scope) -> Element {
                     class: "row pt-2",
                     div {
                         class: "col",
-                        SelectorRow { available_ports: available_ports.clone(), connection: connection.clone(), buffer: port_buffer.clone() }
+                        SelectorRow {
+                            available_ports: available_ports.clone(),
+                            connection: connection.clone(),
+                            port: port.clone(),
+                            session: session.clone(),
+                            highlighter: highlighter.clone(),
+                            recording: recording.clone(),
+                            recorder: recorder.clone(),
+                        }
                     },
                 }
+                div {
+                    class: "row pt-2",
+                    div {
+                        class: "col",
+                        PanelSelector { panel: panel.clone() }
+                    }
+                    if **session_mode != SessionMode::Terminal {
+                        rsx! {
+                            div {
+                                class: "col-auto",
+                                AnsiModeSelector { mode: ansi_mode.clone() }
+                            }
+                        }
+                    }
+                    div {
+                        class: "col-auto",
+                        SessionModeSelector { mode: session_mode.clone() }
+                    }
+                }
                 div {
                     class: "row flex-grow-1 pt-2",
                     min_height: "1rem",
+                    if **session_mode == SessionMode::Console {
+                        rsx! {
+                            div {
+                                class: "col",
+                                Consoles {
+                                    port_buffer: port_buffer.clone(),
+                                    user_buffer: user_buffer.clone(),
+                                    connection: connection.clone(),
+                                    highlighter: highlighter.clone(),
+                                    ansi_mode: **ansi_mode,
+                                }
+                            }
+                        }
+                    }
+                    // Kept mounted while hidden so the screen survives switching modes.
                     div {
-                        class: "col",
-                        Consoles { port_buffer: port_buffer.clone(), user_buffer: user_buffer.clone() }
+                        class: if **session_mode == SessionMode::Terminal { "col" } else { "d-none" },
+                        Terminal { connection: connection.clone() }
+                    }
+                    div {
+                        class: if **session_mode == SessionMode::File { "col h-100" } else { "d-none" },
+                        FileViewer { highlighter: highlighter.clone(), ansi_mode: **ansi_mode }
+                    }
+                    // Kept mounted so the port choices survive switching modes.
+                    div {
+                        class: if **session_mode == SessionMode::Sniffer { "col h-100" } else { "d-none" },
+                        SnifferView {
+                            available_ports: available_ports.clone(),
+                            sniffer: sniffer.clone(),
+                            rules: sniff_rules.clone(),
+                            buffer: sniff_buffer.clone(),
+                            status: sniff_status.clone(),
+                            highlighter: highlighter.clone(),
+                            ansi_mode: **ansi_mode,
+                        }
+                    }
+                    match **panel {
+                        Some(Panel::Script) => rsx! {
+                            div {
+                                class: "col-12 col-lg-4 h-100",
+                                ScriptPanel { connection: connection.clone(), user_buffer: user_buffer.clone() }
+                            }
+                        },
+                        Some(Panel::Responder) => rsx! {
+                            div {
+                                class: "col-12 col-lg-4 h-100",
+                                ResponderPanel { responder: responder.clone() }
+                            }
+                        },
+                        Some(Panel::Highlight) => rsx! {
+                            div {
+                                class: "col-12 col-lg-4 h-100",
+                                HighlightPanel { highlighter: highlighter.clone() }
+                            }
+                        },
+                        Some(Panel::Plot) => rsx! {
+                            div {
+                                class: "col-12 col-lg-4 h-100",
+                                PlotPanel { plot: plot.clone() }
+                            }
+                        },
+                        Some(Panel::Dashboard) => rsx! {
+                            div {
+                                class: "col-12 col-lg-4 h-100",
+                                DashboardPanel { dashboard: dashboard.clone(), port_buffer: port_buffer.clone() }
+                            }
+                        },
+                        Some(Panel::Recording) => rsx! {
+                            div {
+                                class: "col-12 col-lg-4 h-100",
+                                RecordingPanel { recording: recording.clone(), recorder: recorder.clone() }
+                            }
+                        },
+                        Some(Panel::Network) => rsx! {
+                            div {
+                                class: "col-12 col-lg-4 h-100",
+                                NetworkPanel {
+                                    bridge: bridge.clone(),
+                                    bridge_settings: bridge_settings.clone(),
+                                    bridge_status: bridge_status.clone(),
+                                    telnet: telnet.clone(),
+                                    telnet_settings: telnet_settings.clone(),
+                                    telnet_status: telnet_status.clone(),
+                                    websocket: websocket.clone(),
+                                    websocket_settings: websocket_settings.clone(),
+                                    websocket_status: websocket_status.clone(),
+                                }
+                            }
+                        },
+                        Some(Panel::Log) => rsx! {
+                            div {
+                                class: "col-12 col-lg-4 h-100",
+                                LogPanel {
+                                    log: log.clone(),
+                                    connection: connection.clone(),
+                                    viewer: Viewer {
+                                        port_buffer: port_buffer.clone(),
+                                        user_buffer: user_buffer.clone(),
+                                        plot: plot.clone(),
+                                        dashboard: dashboard.clone(),
+                                    },
+                                }
+                            }
+                        },
+                        None => rsx! { Fragment {} },
                     }
                 }
                 div {
@@ -56,7 +312,8 @@ pub fn App(cx: Scope) -> Element {
                         InputBox {
                             user_buffer: user_buffer.clone(),
                             connection: connection.clone(),
-                            port_buffer: port_buffer.clone()
+                            port_buffer: port_buffer.clone(),
+                            highlighter: highlighter.clone()
                         }
                     },
                 }
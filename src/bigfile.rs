@@ -0,0 +1,346 @@
+use std::{
+    fs::File,
+    io,
+    ops::Range,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone};
+use memchr::{memchr_iter, memrchr};
+use memmap2::Mmap;
+use regex::bytes::Regex;
+
+/// One offset is kept for every this many lines, so the index of a file with
+/// a hundred million lines still fits in a few megabytes.
+const INDEX_STEP: usize = 256;
+pub const HEX_WIDTH: usize = 16;
+/// Longer lines are shown in pieces of this many bytes, so that a capture
+/// without line breaks doesn't get decoded and scanned as a whole.
+const MAX_LINE: usize = 4096;
+
+/// A capture opened through a memory map rather than read into memory.
+pub struct BigFile {
+    data: Arc<Mmap>,
+    index: Option<Vec<u64>>,
+}
+
+impl BigFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // Safety: the file is only read. If another process truncates it
+        // while mapped, reads past the new end fault, as with any mmap viewer.
+        let mmap = unsafe { Mmap::map(&file)? };
+        Ok(Self {
+            data: Arc::new(mmap),
+            index: None,
+        })
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    /// A handle to the mapping for work on another thread.
+    pub fn shared(&self) -> Arc<Mmap> {
+        self.data.clone()
+    }
+
+    pub fn set_index(&mut self, index: Vec<u64>) {
+        self.index = Some(index);
+    }
+
+    pub fn index(&self) -> Option<&[u64]> {
+        self.index.as_deref()
+    }
+}
+
+/// Offsets of every `INDEX_STEP`th line start.
+pub fn build_index(data: &[u8]) -> Vec<u64> {
+    let mut index = vec![0];
+    for (n, i) in memchr_iter(b'\n', data).enumerate() {
+        if (n + 1) % INDEX_STEP == 0 && i + 1 < data.len() {
+            index.push(i as u64 + 1);
+        }
+    }
+    index
+}
+
+/// The start of the line containing `offset`, or of its piece if the line
+/// is longer than `MAX_LINE`.
+pub fn line_start(data: &[u8], offset: u64) -> u64 {
+    let offset = (offset as usize).min(data.len());
+    let from = offset.saturating_sub(MAX_LINE);
+    match memrchr(b'\n', &data[from..offset]) {
+        Some(i) => (from + i) as u64 + 1,
+        None => from as u64,
+    }
+}
+
+/// Up to `n` lines starting at `offset`, without their line endings, and
+/// the offset following the last of them. Lines longer than `MAX_LINE` come
+/// in pieces.
+pub fn lines_from(data: &[u8], offset: u64, n: usize) -> (Vec<&[u8]>, u64) {
+    let mut pos = (offset as usize).min(data.len());
+    let mut lines = Vec::new();
+    while lines.len() < n && pos < data.len() {
+        let limit = (pos + MAX_LINE).min(data.len());
+        let line = match memchr::memchr(b'\n', &data[pos..limit]) {
+            Some(i) => {
+                let line = &data[pos..pos + i];
+                pos += i + 1;
+                line.strip_suffix(b"\r").unwrap_or(line)
+            }
+            None => {
+                let line = &data[pos..limit];
+                pos = limit;
+                line
+            }
+        };
+        lines.push(line);
+    }
+    (lines, pos as u64)
+}
+
+/// The start of the line `n` lines before the one starting at `offset`.
+pub fn lines_before(data: &[u8], offset: u64, n: usize) -> u64 {
+    let mut pos = line_start(data, offset);
+    for _ in 0..n {
+        if pos == 0 {
+            break;
+        }
+        pos = line_start(data, pos - 1);
+    }
+    pos
+}
+
+/// The offset of the zero-based line `line`, or of the last line if there
+/// are fewer.
+pub fn line_offset(data: &[u8], index: &[u64], line: u64) -> u64 {
+    let block = (line / INDEX_STEP as u64).min(index.len() as u64 - 1);
+    let skip = line - block * INDEX_STEP as u64;
+    let start = index[block as usize] as usize;
+    let mut offset = start;
+    for (n, i) in memchr_iter(b'\n', &data[start..]).enumerate() {
+        if n as u64 == skip || start + i + 1 == data.len() {
+            break;
+        }
+        offset = start + i + 1;
+    }
+    offset as u64
+}
+
+/// The zero-based number of the line containing `offset`.
+pub fn line_number(data: &[u8], index: &[u64], offset: u64) -> u64 {
+    let block = index.partition_point(|&o| o <= offset).saturating_sub(1);
+    let start = index[block] as usize;
+    let end = (line_start(data, offset) as usize).max(start);
+    (block * INDEX_STEP + memchr_iter(b'\n', &data[start..end]).count()) as u64
+}
+
+/// The next match of `re` at or after `from`, wrapping around to the start.
+pub fn find(data: &[u8], re: &Regex, from: u64) -> Option<Range<u64>> {
+    let from = (from as usize).min(data.len());
+    re.find_at(data, from)
+        .or_else(|| re.find(&data[..from]))
+        .map(|m| m.start() as u64..m.end() as u64)
+}
+
+fn timestamp_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\d{4}-\d\d-\d\d[T ]\d\d:\d\d:\d\d(?:\.\d+)?(?:Z|[+-]\d\d:?\d\d)?").unwrap()
+    })
+}
+
+/// Reads a time as exported (RFC 3339) or typed by hand, e.g.
+/// `2024-01-31 12:00:00`, which is taken as local time.
+pub fn parse_time(s: &str) -> Option<DateTime<FixedOffset>> {
+    let s = s.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Some(t);
+    }
+    [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())
+    .and_then(|t| Local.from_local_datetime(&t).single())
+    .map(|t| t.fixed_offset())
+}
+
+/// The time stamped on the first line at or after `offset` that has one, and
+/// that line's start.
+fn time_at(data: &[u8], offset: u64) -> Option<(DateTime<FixedOffset>, u64)> {
+    let mut pos = offset;
+    // Give up on stretches without timestamps rather than scan the file.
+    for _ in 0..64 {
+        let (lines, next) = lines_from(data, pos, 1);
+        let line = lines.first()?;
+        let time = timestamp_regex()
+            .find(line)
+            .and_then(|m| std::str::from_utf8(m.as_bytes()).ok())
+            .and_then(parse_time);
+        if let Some(t) = time {
+            return Some((t, pos));
+        }
+        pos = next;
+    }
+    None
+}
+
+/// The start of the first line stamped at or after `target`, found by
+/// bisecting the file. Lines are assumed to be in time order.
+pub fn find_time(data: &[u8], target: DateTime<FixedOffset>) -> Option<u64> {
+    let (mut lo, mut hi) = (0u64, data.len() as u64);
+    let mut best = None;
+    while lo < hi {
+        let mid = line_start(data, lo + (hi - lo) / 2);
+        match time_at(data, mid) {
+            Some((t, at)) if t >= target => {
+                best = Some(at);
+                if mid <= lo {
+                    break;
+                }
+                hi = mid;
+            }
+            Some((_, at)) => {
+                let (_, next) = lines_from(data, at, 1);
+                if next <= lo {
+                    break;
+                }
+                lo = next;
+            }
+            None => hi = mid,
+        }
+    }
+    best
+}
+
+/// Classic hex dump rows: offset, bytes and printable ASCII.
+pub fn hex_rows(data: &[u8], offset: u64, rows: usize) -> Vec<String> {
+    let start = (offset as usize).min(data.len());
+    data[start..]
+        .chunks(HEX_WIDTH)
+        .take(rows)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex = chunk
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+            format!(
+                "{:010x}  {hex:<width$}  {ascii}",
+                start + i * HEX_WIDTH,
+                width = HEX_WIDTH * 3 - 1
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &[u8] = b"one\r\ntwo\nthree\n";
+
+    #[test]
+    fn lines_strip_endings() {
+        let (lines, next) = lines_from(DATA, 0, 2);
+        assert_eq!(lines, [&b"one"[..], b"two"]);
+        assert_eq!(next, 9);
+        let (lines, next) = lines_from(DATA, next, 5);
+        assert_eq!(lines, [&b"three"[..]]);
+        assert_eq!(next, DATA.len() as u64);
+    }
+
+    #[test]
+    fn long_lines_come_in_pieces() {
+        let data = vec![b'x'; MAX_LINE * 2 + 10];
+        let (lines, _) = lines_from(&data, 0, 10);
+        let lengths = lines.iter().map(|l| l.len()).collect::<Vec<_>>();
+        assert_eq!(lengths, [MAX_LINE, MAX_LINE, 10]);
+        assert_eq!(line_start(&data, data.len() as u64), (MAX_LINE + 10) as u64);
+    }
+
+    #[test]
+    fn line_starts() {
+        assert_eq!(line_start(DATA, 0), 0);
+        assert_eq!(line_start(DATA, 7), 5);
+        assert_eq!(line_start(DATA, 100), DATA.len() as u64);
+        assert_eq!(lines_before(DATA, 12, 1), 5);
+        assert_eq!(lines_before(DATA, 12, 5), 0);
+    }
+
+    #[test]
+    fn index_and_line_numbers() {
+        let data = (0..1000).map(|i| format!("{i}\n")).collect::<String>();
+        let data = data.as_bytes();
+        let index = build_index(data);
+        assert_eq!(index.len(), 1000 / INDEX_STEP + 1);
+        for line in [0, 1, INDEX_STEP as u64, 999] {
+            let offset = line_offset(data, &index, line);
+            assert_eq!(
+                lines_from(data, offset, 1).0[0],
+                line.to_string().as_bytes()
+            );
+            assert_eq!(line_number(data, &index, offset), line);
+        }
+    }
+
+    #[test]
+    fn line_offset_clamps_to_the_last_line() {
+        let index = build_index(DATA);
+        assert_eq!(line_offset(DATA, &index, 2), 9);
+        assert_eq!(line_offset(DATA, &index, 100), 9);
+        assert_eq!(line_offset(b"a\nb", &build_index(b"a\nb"), 100), 2);
+    }
+
+    #[test]
+    fn find_wraps_around() {
+        let re = Regex::new("t").unwrap();
+        assert_eq!(find(DATA, &re, 0), Some(5..6));
+        assert_eq!(find(DATA, &re, 6), Some(9..10));
+        assert_eq!(find(DATA, &re, 10), Some(5..6));
+    }
+
+    #[test]
+    fn find_time_bisects() {
+        let data = (0..60)
+            .map(|s| format!("2024-01-31T12:00:{s:02}Z line {s}\n"))
+            .collect::<String>();
+        let data = data.as_bytes();
+        let target = parse_time("2024-01-31T12:00:30Z").unwrap();
+        let at = find_time(data, target).unwrap();
+        assert!(lines_from(data, at, 1).0[0].ends_with(b"line 30"));
+        assert_eq!(
+            find_time(data, parse_time("2024-02-02 00:00").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn hex_dump() {
+        let rows = hex_rows(b"AB\n", 0, 1);
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].starts_with("0000000000  41 42 0a"));
+        assert!(rows[0].ends_with("  AB."));
+    }
+}
@@ -0,0 +1,181 @@
+use std::{io, net::SocketAddr};
+
+use log::*;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc::UnboundedSender,
+    },
+};
+
+use serial_monitor::record::{Direction, Record};
+
+use crate::{telnet, websocket};
+
+/// How many records a slow client may fall behind before it misses data.
+pub const BACKLOG: usize = 1024;
+const READ_BUF_SIZE: usize = 1024;
+
+/// Who may write to the port through the bridge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Policy {
+    /// Clients only see what the port sends.
+    ReadOnly,
+    /// The longest connected client may write, the others only watch.
+    #[default]
+    SingleWriter,
+    /// Every client may write.
+    Shared,
+}
+
+impl Policy {
+    pub const ALL: [Policy; 3] = [Policy::ReadOnly, Policy::SingleWriter, Policy::Shared];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Policy::ReadOnly => "Read-only",
+            Policy::SingleWriter => "One writer",
+            Policy::Shared => "All write",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// Bytes are passed through untouched.
+    Raw,
+    /// For telnet clients, see [`crate::telnet`].
+    Telnet,
+    /// JSON records for browsers, see [`crate::websocket`].
+    WebSocket,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BridgeSettings {
+    pub address: String,
+    pub policy: Policy,
+    pub protocol: Protocol,
+    /// Let clients on other machines write too. Without it they only watch,
+    /// whatever the policy.
+    pub remote_writes: bool,
+}
+
+impl BridgeSettings {
+    pub fn raw() -> Self {
+        Self {
+            address: "127.0.0.1:7000".to_string(),
+            policy: Policy::default(),
+            protocol: Protocol::Raw,
+            remote_writes: false,
+        }
+    }
+
+    pub fn telnet() -> Self {
+        Self {
+            address: "127.0.0.1:2323".to_string(),
+            policy: Policy::default(),
+            protocol: Protocol::Telnet,
+            remote_writes: false,
+        }
+    }
+
+    pub fn websocket() -> Self {
+        Self {
+            address: "127.0.0.1:9001".to_string(),
+            policy: Policy::default(),
+            protocol: Protocol::WebSocket,
+            remote_writes: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BridgeStatus {
+    pub listening: Option<SocketAddr>,
+    /// Connected clients by id, oldest first.
+    pub clients: Vec<(usize, SocketAddr)>,
+    pub error: Option<String>,
+}
+
+impl BridgeStatus {
+    pub fn may_write(&self, settings: &BridgeSettings, id: usize) -> bool {
+        let allowed = |addr: &SocketAddr| settings.remote_writes || addr.ip().is_loopback();
+        let mut writers = self.clients.iter().filter(|c| allowed(&c.1));
+        match settings.policy {
+            Policy::ReadOnly => false,
+            Policy::SingleWriter => writers.next().map(|c| c.0) == Some(id),
+            Policy::Shared => writers.any(|c| c.0 == id),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Event {
+    Joined(usize, SocketAddr),
+    Left(usize),
+    /// Bytes a client sent, to be written to the port if it may.
+    Data(usize, Vec<u8>),
+    /// A client asked for a break on the line.
+    Break(usize),
+}
+
+/// Accepts clients on `listener`. Records sent on `data` go out to all of
+/// them (only what was received, except over WebSocket); what they send
+/// comes back as [`Event`]s. Clients are disconnected once `data` is
+/// dropped.
+pub async fn serve(
+    listener: TcpListener,
+    protocol: Protocol,
+    data: broadcast::Sender<Record>,
+    events: UnboundedSender<Event>,
+) -> io::Result<()> {
+    let mut next_id = 0;
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let id = next_id;
+        next_id += 1;
+        info!("Bridge client {addr} connected ({protocol:?})");
+        let _ = events.send(Event::Joined(id, addr));
+        let rx = data.subscribe();
+        let events = events.clone();
+        tokio::spawn(async move {
+            let res = match protocol {
+                Protocol::Raw => client(id, stream, rx, &events).await,
+                Protocol::Telnet => telnet::client(id, stream, rx, &events).await,
+                Protocol::WebSocket => websocket::client(id, stream, rx, &events).await,
+            };
+            if let Err(e) = res {
+                debug!("{:?}", e);
+            }
+            info!("Bridge client {addr} disconnected");
+            let _ = events.send(Event::Left(id));
+        });
+    }
+}
+
+async fn client(
+    id: usize,
+    mut stream: TcpStream,
+    mut data: broadcast::Receiver<Record>,
+    events: &UnboundedSender<Event>,
+) -> io::Result<()> {
+    let mut buf = [0; READ_BUF_SIZE];
+    loop {
+        tokio::select! {
+            n = stream.read(&mut buf) => match n? {
+                0 => return Ok(()),
+                n => {
+                    let _ = events.send(Event::Data(id, buf[..n].to_vec()));
+                }
+            },
+            record = data.recv() => match record {
+                Ok(record) if record.direction == Direction::Rx => stream.write_all(&record.data).await?,
+                Ok(_) => (),
+                Err(RecvError::Lagged(n)) => warn!("Bridge client {id} missed {n} records"),
+                Err(RecvError::Closed) => return Ok(()),
+            },
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    io::{self, Write},
+    process::ExitCode,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use log::*;
+use regex::Regex;
+use tokio::{
+    io::AsyncReadExt,
+    sync::mpsc::unbounded_channel,
+    time::{interval, sleep_until, Duration, Instant},
+};
+use tokio_serial::{DataBits, FlowControl, Parity, StopBits};
+
+use serial_monitor::{
+    connection::{Connection, DEFAULT_BR},
+    handle::LineSettings,
+    record::{Direction, Record},
+};
+
+#[cfg(feature = "gui")]
+use crate::control::Endpoint;
+use crate::READ_FREQ;
+
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Without a command the desktop app is started.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Let scripts drive the desktop app over JSON-RPC on this Unix socket
+    /// or loopback address.
+    #[cfg(feature = "gui")]
+    #[arg(long, value_name = "PATH|127.0.0.1:PORT")]
+    pub control: Option<Endpoint>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print what a port sends and forward stdin to it, without a window.
+    #[command(after_help = "\
+Exit status:
+  0  --exit-on matched, the timeout ran out without --exit-on, or stdin closed with --exit-on-eof
+  1  the port could not be opened or was lost
+  2  invalid arguments
+  3  the timeout ran out before --exit-on matched
+  130  interrupted")]
+    Headless(HeadlessArgs),
+    /// Run the monitor in the terminal, e.g. over SSH.
+    #[cfg(feature = "tui")]
+    Tui(TuiArgs),
+}
+
+#[derive(Args)]
+pub struct HeadlessArgs {
+    /// Serial port, e.g. /dev/ttyUSB0 or COM3.
+    #[arg(short, long)]
+    pub port: String,
+    #[arg(short, long, default_value_t = DEFAULT_BR)]
+    pub baud: u32,
+    #[command(flatten)]
+    pub line: LineArgs,
+    /// Prefix every received line with its time of arrival.
+    #[arg(short, long)]
+    pub timestamps: bool,
+    /// Print received data as hex bytes, one read per line.
+    #[arg(short = 'x', long)]
+    pub hex: bool,
+    /// Exit once received data matches this regular expression.
+    #[arg(short, long, value_name = "REGEX")]
+    pub exit_on: Option<Regex>,
+    /// Give up after this many seconds.
+    #[arg(long, value_name = "SECONDS", value_parser = parse_timeout)]
+    pub timeout: Option<Duration>,
+    /// Exit once stdin is closed instead of waiting for the port.
+    #[arg(long)]
+    pub exit_on_eof: bool,
+}
+
+#[cfg(feature = "tui")]
+#[derive(Args)]
+pub struct TuiArgs {
+    /// Port to connect to at start; one can also be picked with F2.
+    #[arg(short, long)]
+    pub port: Option<String>,
+    #[arg(short, long, default_value_t = DEFAULT_BR)]
+    pub baud: u32,
+    #[command(flatten)]
+    pub line: LineArgs,
+}
+
+#[derive(Args)]
+pub struct LineArgs {
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u8).range(5..=8))]
+    pub data_bits: u8,
+    #[arg(long, value_enum, default_value_t = ParityArg::None)]
+    pub parity: ParityArg,
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=2))]
+    pub stop_bits: u8,
+    #[arg(long, value_enum, default_value_t = FlowControlArg::None)]
+    pub flow_control: FlowControlArg,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ParityArg {
+    None,
+    Odd,
+    Even,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum FlowControlArg {
+    None,
+    Software,
+    Hardware,
+}
+
+impl LineArgs {
+    pub fn settings(&self) -> LineSettings {
+        LineSettings {
+            data_bits: match self.data_bits {
+                5 => DataBits::Five,
+                6 => DataBits::Six,
+                7 => DataBits::Seven,
+                _ => DataBits::Eight,
+            },
+            parity: match self.parity {
+                ParityArg::None => Parity::None,
+                ParityArg::Odd => Parity::Odd,
+                ParityArg::Even => Parity::Even,
+            },
+            stop_bits: match self.stop_bits {
+                2 => StopBits::Two,
+                _ => StopBits::One,
+            },
+            flow_control: match self.flow_control {
+                FlowControlArg::None => FlowControl::None,
+                FlowControlArg::Software => FlowControl::Software,
+                FlowControlArg::Hardware => FlowControl::Hardware,
+            },
+        }
+    }
+}
+
+/// Longer timeouts would overflow the deadline on some platforms, and are
+/// no different from none.
+const MAX_TIMEOUT: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+fn parse_timeout(s: &str) -> Result<Duration, String> {
+    let secs: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    match Duration::try_from_secs_f64(secs) {
+        Ok(t) if !t.is_zero() && t <= MAX_TIMEOUT => Ok(t),
+        _ => Err("expected a positive number of seconds, at most a year".to_string()),
+    }
+}
+
+const EXIT_FAILED: u8 = 1;
+const EXIT_TIMEOUT: u8 = 3;
+const EXIT_INTERRUPTED: u8 = 130;
+/// Writes are queued to the port's IO task, so give the last of stdin a
+/// moment to go out before exiting on EOF.
+const DRAIN_TIME: Duration = Duration::from_millis(100);
+/// How much of an unterminated line `--exit-on` patterns can match against.
+const MAX_TAIL: usize = 64 * 1024;
+
+/// Writes received data to stdout in the requested form.
+struct Printer {
+    timestamps: bool,
+    hex: bool,
+    at_line_start: bool,
+}
+
+impl Printer {
+    fn print(&mut self, out: &mut impl Write, record: &Record) -> io::Result<()> {
+        if self.hex {
+            if self.timestamps {
+                write!(out, "[{}] ", record.timestamp())?;
+            }
+            writeln!(out, "{}", record.hex())?;
+        } else if self.timestamps {
+            for piece in record.data.split_inclusive(|&b| b == b'\n') {
+                if self.at_line_start {
+                    write!(out, "[{}] ", record.timestamp())?;
+                }
+                out.write_all(piece)?;
+                self.at_line_start = piece.ends_with(b"\n");
+            }
+        } else {
+            out.write_all(&record.data)?;
+        }
+        out.flush()
+    }
+}
+
+pub async fn headless(args: HeadlessArgs) -> ExitCode {
+    let mut connection = Connection::new(args.baud);
+    // Records go straight to stdout, so keeping them would only grow.
+    connection.set_logging(false);
+    if let Err(e) = connection.set_line_settings(args.line.settings()) {
+        error!("{:?}", e);
+    }
+    if let Err(e) = connection.open(&args.port) {
+        eprintln!("Failed to open {}: {e}", args.port);
+        return ExitCode::from(EXIT_FAILED);
+    }
+    info!("Connected to {}", args.port);
+    let mut records = connection.subscribe_records();
+
+    let (stdin_tx, mut stdin_rx) = unbounded_channel::<Vec<u8>>();
+    tokio::spawn(async move {
+        let mut stdin = tokio::io::stdin();
+        let mut buf = [0; 1024];
+        // An empty chunk tells the loop below that stdin was closed.
+        while let Ok(n) = stdin.read(&mut buf).await {
+            if stdin_tx.send(buf[..n].to_vec()).is_err() || n == 0 {
+                break;
+            }
+        }
+    });
+
+    let mut printer = Printer {
+        timestamps: args.timestamps,
+        hex: args.hex,
+        at_line_start: true,
+    };
+    let mut stdout = io::stdout().lock();
+    // Received text not yet followed by a newline, so patterns can match
+    // across reads.
+    let mut tail = String::new();
+    let deadline = args.timeout.map(|t| Instant::now() + t);
+    let mut interval = interval(READ_FREQ);
+    let mut stdin_open = true;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                // Take every frame that arrived since the last tick.
+                loop {
+                    match connection.read() {
+                        Ok(data) if data.is_empty() => break,
+                        Ok(_) => (),
+                        Err(e) => {
+                            eprintln!("Lost {}: {e}", args.port);
+                            return ExitCode::from(EXIT_FAILED);
+                        }
+                    }
+                }
+                if !connection.is_connected() {
+                    eprintln!("Lost {}", args.port);
+                    return ExitCode::from(EXIT_FAILED);
+                }
+                while let Ok(record) = records.try_recv() {
+                    if record.direction != Direction::Rx {
+                        continue;
+                    }
+                    if let Err(e) = printer.print(&mut stdout, &record) {
+                        // Usually a closed pipe, e.g. `| head`.
+                        debug!("{:?}", e);
+                        return ExitCode::SUCCESS;
+                    }
+                    let Some(re) = &args.exit_on else {
+                        continue;
+                    };
+                    tail.push_str(&record.text());
+                    if re.is_match(&tail) {
+                        return ExitCode::SUCCESS;
+                    }
+                    if let Some(i) = tail.rfind('\n') {
+                        tail.drain(..=i);
+                    }
+                    if tail.len() > MAX_TAIL {
+                        let mut start = tail.len() - MAX_TAIL;
+                        while !tail.is_char_boundary(start) {
+                            start += 1;
+                        }
+                        tail.drain(..start);
+                    }
+                }
+            }
+            data = stdin_rx.recv(), if stdin_open => match data {
+                Some(data) if !data.is_empty() => {
+                    if let Err(e) = connection.write_bytes(&data) {
+                        eprintln!("Failed to write to {}: {e}", args.port);
+                        return ExitCode::from(EXIT_FAILED);
+                    }
+                }
+                _ => {
+                    stdin_open = false;
+                    if args.exit_on_eof {
+                        tokio::time::sleep(DRAIN_TIME).await;
+                        return ExitCode::SUCCESS;
+                    }
+                }
+            },
+            _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                if args.exit_on.is_some() {
+                    eprintln!("Timed out waiting for a match");
+                    return ExitCode::from(EXIT_TIMEOUT);
+                }
+                return ExitCode::SUCCESS;
+            }
+            _ = tokio::signal::ctrl_c() => return ExitCode::from(EXIT_INTERRUPTED),
+        }
+    }
+}
@@ -1,68 +1,402 @@
-use dioxus::prelude::*;
+use std::{collections::HashMap, ops::Range};
+
+use dioxus::{
+    html::input_data::keyboard_types::{Key, Modifiers},
+    prelude::*,
+};
+
+use serial_monitor::{
+    ansi::{self, AnsiMode},
+    connection::Connection,
+    highlight::Highlighter,
+    styled,
+};
+
+use crate::{
+    filter::{Filter, MAX_CONTEXT},
+    search::Search,
+};
 
 #[inline_props]
 pub fn Consoles(
     cx: Scope,
     port_buffer: UseRef<Vec<String>>,
     user_buffer: UseRef<Vec<String>>,
+    connection: UseRef<Connection>,
+    highlighter: UseRef<Highlighter>,
+    ansi_mode: AnsiMode,
 ) -> Element {
     render! {
         div {
             class: "row g-2 h-100",
             div {
                 class: "col-12 col-md",
-                Console { id: 0, buffer: user_buffer.clone() }
+                Console { id: 0, buffer: user_buffer.clone(), highlighter: highlighter.clone(), ansi_mode: *ansi_mode }
             },
             div {
                 class: "col-12 col-md",
-                Console { id: 1, buffer: port_buffer.clone() }
+                Console { id: 1, buffer: port_buffer.clone(), log: connection.clone(), highlighter: highlighter.clone(), ansi_mode: *ansi_mode }
             },
         }
     }
 }
 
 #[inline_props]
-fn Console(cx: Scope, id: usize, buffer: UseRef<Vec<String>>) -> Element {
+pub fn AnsiModeSelector(cx: Scope, mode: UseState<AnsiMode>) -> Element {
+    render! {
+        div {
+            class: "input-group input-group-sm",
+            span { class: "input-group-text", "ANSI" }
+            select {
+                class: "form-select",
+                value: mode.name(),
+                onchange: move |e| {
+                    if let Some(m) = AnsiMode::ALL.iter().find(|m| m.name() == e.value) {
+                        mode.set(*m);
+                    }
+                },
+                AnsiMode::ALL.iter().map(|m| rsx! { option { value: m.name(), m.name() } })
+            }
+        }
+    }
+}
+
+#[inline_props]
+pub fn Console(
+    cx: Scope,
+    id: usize,
+    buffer: UseRef<Vec<String>>,
+    log: Option<UseRef<Connection>>,
+    highlighter: UseRef<Highlighter>,
+    ansi_mode: AnsiMode,
+) -> Element {
     let element_id = format!("console_{id}");
+    let hit_id = format!("console_{id}_hit");
     let eval = use_eval(cx).clone();
-    let script = format!(
-        r#"
-        var elements = document.querySelectorAll("[id='{element_id}']");
-        for (var i = 0; i < elements.length; ++i) {{
-            elements[i].scrollTop = elements[i].scrollHeight;
-        }}
-        // element.scrollTop = element.scrollHeight;
-        "#
-    );
-    cx.push_future(async move {
-        eval(script.as_ref()).unwrap();
-    });
+
+    let filter = use_state(cx, Filter::default);
+    let search = use_state(cx, || None::<Search>);
+    let current = use_state(cx, || 0usize);
+    let scrolled_to = use_ref(cx, || None::<(usize, usize)>);
 
     let content = buffer.read().iter().cloned().collect::<String>();
+    let lines = ansi::parse(&content, *ansi_mode);
+    let texts = lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>();
+    let (mut visible, invalid) = match filter.get().apply(&texts) {
+        Ok(v) => (v, false),
+        Err(_) => ((0..lines.len()).collect(), true),
+    };
+    // Search covers the whole scrollback; a hit the filter hides is shown
+    // while it is the current one.
+    let (matches, search_invalid) = match search.get().as_ref().map(|s| s.find(&texts)) {
+        Some(Ok(m)) => (m, false),
+        Some(Err(_)) => (Vec::new(), true),
+        None => (Vec::new(), false),
+    };
+    let hit = (!matches.is_empty()).then(|| (**current).min(matches.len() - 1));
+    let shown = visible.len();
+    if let Some(h) = hit {
+        if let Err(at) = visible.binary_search(&matches[h].line) {
+            visible.insert(at, matches[h].line);
+        }
+    }
+    let mut marks = HashMap::<usize, Vec<_>>::new();
+    for (k, m) in matches.iter().enumerate() {
+        marks
+            .entry(m.line)
+            .or_default()
+            .push((m.range.clone(), Some(k) == hit));
+    }
+
+    // Auto-scroll follows new data unless a search is open, in which case
+    // the view only moves when the current hit changes.
+    let script = match (search.is_some(), hit) {
+        (false, _) => Some(format!(
+            r#"
+            var elements = document.querySelectorAll("[id='{element_id}']");
+            for (var i = 0; i < elements.length; ++i) {{
+                elements[i].scrollTop = elements[i].scrollHeight;
+            }}
+            "#
+        )),
+        (true, Some(h)) => {
+            let target = (matches[h].line, matches[h].range.start);
+            if *scrolled_to.read() != Some(target) {
+                *scrolled_to.write_silent() = Some(target);
+                Some(format!(
+                    r#"
+                    var element = document.getElementById("{hit_id}");
+                    if (element) {{
+                        element.scrollIntoView({{ block: "center" }});
+                    }}
+                    "#
+                ))
+            } else {
+                None
+            }
+        }
+        (true, None) => None,
+    };
+    if let Some(script) = script {
+        cx.push_future(async move {
+            eval(script.as_ref()).unwrap();
+        });
+    }
+
+    let lines = &lines;
+    let marks = &marks;
+    let hit_id = &hit_id;
 
     render! {
         div {
-            class: "h-100 position-relative",
-            textarea {
-                id: "{element_id}",
-                class: "form-control w-100 h-100",
-                font_size: "0.875rem",
-                readonly: true,
-                resize: "none",
-                "{content}"
+            class: "h-100 d-flex flex-column gap-1",
+            onkeydown: move |e| {
+                if e.modifiers().contains(Modifiers::CONTROL)
+                    && e.key() == Key::Character("f".to_string())
+                    && search.is_none()
+                {
+                    search.set(Some(Search::default()));
+                }
+            },
+            FilterBar { filter: filter.clone(), invalid: invalid, shown: shown, total: lines.len() }
+            if search.is_some() {
+                rsx! {
+                    SearchBar {
+                        search: search.clone(),
+                        current: current.clone(),
+                        scrolled_to: scrolled_to.clone(),
+                        hit: hit.unwrap_or(0),
+                        count: matches.len(),
+                        invalid: search_invalid,
+                    }
+                }
+            }
+            div {
+                class: "flex-grow-1 position-relative",
+                min_height: "1rem",
+                div {
+                    id: "{element_id}",
+                    class: "form-control w-100 h-100 overflow-auto",
+                    tabindex: "0",
+                    font_size: "0.875rem",
+                    white_space: "pre-wrap",
+                    visible.iter().enumerate().map(|(n, &i)| {
+                        let gap = filter.context > 0 && n > 0 && visible[n - 1] + 1 != i;
+                        let marks = marks.get(&i).cloned().unwrap_or_default();
+                        rsx! {
+                            div {
+                                key: "{i}",
+                                class: if gap { "border-top border-secondary" } else { "" },
+                                min_height: "1.2em",
+                                StyledLine { line: lines[i].clone(), highlighter: highlighter.clone(), marks: marks, hit_id: hit_id.clone() }
+                            }
+                        }
+                    })
+                }
+                button {
+                    class: "btn btn-outline-danger position-absolute",
+                    font_size: "0.9rem",
+                    top: "10px",
+                    right: "10px",
+                    onclick: move |_| {
+                        match id {
+                            0 => buffer.with_mut(|x| x.clear()),
+                            _ => buffer.with_mut(|x| x.clear())
+                        }
+                        // Downloads start afresh too.
+                        if let Some(log) = log {
+                            log.write_silent().clear_log();
+                        }
+                    },
+                    "Clear"
+                }
+            }
+        }
+    }
+}
+
+/// One line of console output, coloured by its ANSI styles and the
+/// highlight rules, with search hits marked.
+#[inline_props]
+pub fn StyledLine(
+    cx: Scope,
+    line: ansi::Line,
+    highlighter: UseRef<Highlighter>,
+    marks: Vec<(Range<usize>, bool)>,
+    hit_id: String,
+) -> Element {
+    let highlighter = highlighter.read();
+    let highlighter = &*highlighter;
+    render! {
+        styled::pieces(line, highlighter, marks).into_iter().map(|p| {
+            let style = p.css(highlighter);
+            match p.mark {
+                Some(true) => rsx! { span { id: "{hit_id}", class: "bg-warning text-dark", style: "{style}", "{p.text}" } },
+                Some(false) => rsx! { span { class: "bg-warning-subtle", style: "{style}", "{p.text}" } },
+                None if !style.is_empty() => rsx! { span { style: "{style}", "{p.text}" } },
+                None => rsx! { "{p.text}" },
+            }
+        })
+    }
+}
+
+#[inline_props]
+fn SearchBar(
+    cx: Scope,
+    search: UseState<Option<Search>>,
+    current: UseState<usize>,
+    scrolled_to: UseRef<Option<(usize, usize)>>,
+    hit: usize,
+    count: usize,
+    invalid: bool,
+) -> Element {
+    let s = search.get().clone().unwrap_or_default();
+    let count = *count;
+    let hit = *hit;
+    let position = if count > 0 { hit + 1 } else { 0 };
+    let step = move |forward: bool| {
+        if count > 0 {
+            current.set(if forward {
+                (hit + 1) % count
+            } else {
+                (hit + count - 1) % count
+            });
+        }
+    };
+    let toggle_class = |on: bool| {
+        if on {
+            "btn btn-secondary"
+        } else {
+            "btn btn-outline-secondary"
+        }
+    };
+    let input_class = if *invalid {
+        "form-control is-invalid"
+    } else {
+        "form-control"
+    };
+
+    render! {
+        div {
+            class: "input-group input-group-sm",
+            input {
+                class: "{input_class}",
+                value: "{s.query}",
+                placeholder: "Find",
+                spellcheck: "false",
+                autofocus: true,
+                oninput: move |e| {
+                    current.set(0);
+                    scrolled_to.set(None);
+                    search.modify(|s| s.clone().map(|s| Search { query: e.value.clone(), ..s }));
+                },
+                onkeydown: move |e| match e.key() {
+                    Key::Enter => step(!e.modifiers().contains(Modifiers::SHIFT)),
+                    Key::Escape => search.set(None),
+                    _ => (),
+                },
+            }
+            span { class: "input-group-text", "{position}/{count}" }
+            button {
+                class: toggle_class(s.case_sensitive),
+                title: "Match case",
+                onclick: move |_| search.modify(|s| s.clone().map(|s| Search { case_sensitive: !s.case_sensitive, ..s })),
+                "Aa"
+            }
+            button {
+                class: toggle_class(s.regex),
+                title: "Regular expression",
+                onclick: move |_| search.modify(|s| s.clone().map(|s| Search { regex: !s.regex, ..s })),
+                ".*"
             }
             button {
-                class: "btn btn-outline-danger position-absolute",
-                font_size: "0.9rem",
-                top: "10px",
-                right: "10px",
-                onclick: move |_| {
-                    match id {
-                        0 => buffer.with_mut(|x| x.clear()),
-                        _ => buffer.with_mut(|x| x.clear())
+                class: "btn btn-outline-secondary",
+                title: "Previous match (Shift+Enter)",
+                onclick: move |_| step(false),
+                "↑"
+            }
+            button {
+                class: "btn btn-outline-secondary",
+                title: "Next match (Enter)",
+                onclick: move |_| step(true),
+                "↓"
+            }
+            button {
+                class: "btn btn-outline-secondary",
+                title: "Close (Esc)",
+                onclick: move |_| search.set(None),
+                "✕"
+            }
+        }
+    }
+}
+
+#[inline_props]
+fn FilterBar(
+    cx: Scope,
+    filter: UseState<Filter>,
+    invalid: bool,
+    shown: usize,
+    total: usize,
+) -> Element {
+    let toggle_class = |on: bool| {
+        if on {
+            "btn btn-secondary"
+        } else {
+            "btn btn-outline-secondary"
+        }
+    };
+    let input_class = if *invalid {
+        "form-control is-invalid"
+    } else {
+        "form-control"
+    };
+
+    render! {
+        div {
+            class: "input-group input-group-sm",
+            input {
+                class: "{input_class}",
+                value: "{filter.query}",
+                placeholder: "Filter",
+                spellcheck: "false",
+                oninput: move |e| filter.modify(|f| Filter { query: e.value.clone(), ..f.clone() }),
+            }
+            if filter.is_active() {
+                rsx! { span { class: "input-group-text", "{shown}/{total}" } }
+            }
+            button {
+                class: toggle_class(filter.regex),
+                title: "Regular expression",
+                onclick: move |_| filter.modify(|f| Filter { regex: !f.regex, ..f.clone() }),
+                ".*"
+            }
+            button {
+                class: toggle_class(filter.invert),
+                title: "Invert match",
+                onclick: move |_| filter.modify(|f| Filter { invert: !f.invert, ..f.clone() }),
+                "!"
+            }
+            input {
+                class: "form-control",
+                r#type: "number",
+                min: "0",
+                max: "{MAX_CONTEXT}",
+                max_width: "4.5rem",
+                title: "Context lines",
+                value: "{filter.context}",
+                oninput: move |e| {
+                    if let Ok(context) = e.value.parse::<usize>() {
+                        let context = context.min(MAX_CONTEXT);
+                        filter.modify(|f| Filter { context, ..f.clone() });
                     }
                 },
-                "Clear"
+            }
+            button {
+                class: "btn btn-outline-secondary",
+                title: "Clear filter",
+                onclick: move |_| filter.set(Filter::default()),
+                "✕"
             }
         }
     }
@@ -0,0 +1,157 @@
+use dioxus::prelude::*;
+use tokio::time::{interval, Duration};
+
+use serial_monitor::plot;
+
+use crate::{
+    api,
+    dashboard::{self, Dashboard, Thresholds},
+};
+
+const REFRESH_FREQ: Duration = Duration::from_millis(250);
+const FLASH_STYLE: &str =
+    "@keyframes alarm-flash { 50% { background-color: rgba(220, 53, 69, 0.45); } }";
+
+fn format_value(v: f64) -> String {
+    let s = format!("{v:.3}");
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[inline_props]
+pub fn DashboardPanel(
+    cx: Scope,
+    dashboard: UseRef<Dashboard>,
+    port_buffer: UseRef<Vec<String>>,
+) -> Element {
+    let update = cx.schedule_update();
+    use_future(cx, (), |_| async move {
+        let mut interval = interval(REFRESH_FREQ);
+        loop {
+            interval.tick().await;
+            update();
+        }
+    });
+
+    let count = dashboard.read().metrics().len();
+
+    render! {
+        style { FLASH_STYLE }
+        div {
+            class: "h-100 d-flex flex-column gap-2 overflow-auto",
+            div {
+                class: "d-flex gap-2 align-items-center",
+                span { class: "form-text", "Values from key=value pairs in received lines." }
+                div {
+                    class: "btn-group btn-group-sm ms-auto",
+                    button {
+                        class: "btn btn-outline-secondary",
+                        onclick: move |_| {
+                            let svg = dashboard::export_svg(&dashboard.read());
+                            cx.spawn(api::export("SVG", "svg", "dashboard.svg", svg));
+                        },
+                        "SVG"
+                    }
+                    button {
+                        class: "btn btn-outline-secondary",
+                        onclick: move |_| {
+                            match dashboard::export_csv(&dashboard.read()) {
+                                Ok(csv) => cx.spawn(api::export("CSV", "csv", "dashboard.csv", csv)),
+                                Err(e) => cx.spawn(async move {
+                                    api::show_error_dialog("Failed to export CSV", &format!("{e}")).await;
+                                }),
+                            }
+                        },
+                        "CSV"
+                    }
+                }
+                button {
+                    class: "btn btn-sm btn-outline-danger",
+                    onclick: move |_| dashboard.with_mut(|d| d.clear()),
+                    "Reset"
+                }
+            }
+            div {
+                class: "row row-cols-1 row-cols-sm-2 g-2",
+                (0..count).map(|i| rsx! {
+                    Tile { key: "{i}", index: i, dashboard: dashboard.clone(), port_buffer: port_buffer.clone() }
+                })
+            }
+        }
+    }
+}
+
+#[inline_props]
+fn Tile(
+    cx: Scope,
+    index: usize,
+    dashboard: UseRef<Dashboard>,
+    port_buffer: UseRef<Vec<String>>,
+) -> Element {
+    let index = *index;
+    let d = dashboard.read();
+    let m = d.metrics().get(index)?;
+    let name = m.name.clone();
+    let last = format_value(m.last);
+    let (min, avg, max) = (
+        format_value(m.min),
+        format_value(m.average()),
+        format_value(m.max),
+    );
+    let sparkline = dashboard::sparkline_svg(&m.history, plot::color(index));
+    let thresholds = m.thresholds;
+    let low = thresholds.low.map(format_value).unwrap_or_default();
+    let high = thresholds.high.map(format_value).unwrap_or_default();
+    let alarm = m.in_alarm();
+    drop(d);
+
+    let set = move |thresholds: Thresholds| {
+        let event = dashboard.with_mut(|d| d.set_thresholds(index, thresholds));
+        if let Some(e) = event {
+            api::mark(port_buffer, "alarm", &e.describe());
+        }
+    };
+
+    render! {
+        div {
+            class: "col",
+            div {
+                class: if alarm { "card border-danger" } else { "card" },
+                style: if alarm { "animation: alarm-flash 1s step-start infinite;" } else { "" },
+                div {
+                    class: "card-body p-2",
+                    div {
+                        class: "d-flex justify-content-between align-items-baseline",
+                        span { class: "text-secondary small text-truncate", "{name}" }
+                        span { class: "fs-5 font-monospace", "{last}" }
+                    }
+                    div { dangerous_inner_html: "{sparkline}" }
+                    div {
+                        class: "d-flex justify-content-between small text-secondary font-monospace",
+                        span { title: "min", "↓{min}" }
+                        span { title: "average", "⌀{avg}" }
+                        span { title: "max", "↑{max}" }
+                    }
+                    div {
+                        class: "input-group input-group-sm mt-1",
+                        input {
+                            class: "form-control",
+                            r#type: "number",
+                            placeholder: "low",
+                            title: "Alarm below",
+                            value: "{low}",
+                            onchange: move |e| set(Thresholds { low: e.value.parse().ok(), ..thresholds }),
+                        }
+                        input {
+                            class: "form-control",
+                            r#type: "number",
+                            placeholder: "high",
+                            title: "Alarm above",
+                            value: "{high}",
+                            onchange: move |e| set(Thresholds { high: e.value.parse().ok(), ..thresholds }),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
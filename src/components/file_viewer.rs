@@ -0,0 +1,454 @@
+use std::{ops::Range, path::PathBuf, sync::Arc};
+
+use dioxus::{html::input_data::keyboard_types::Key, prelude::*};
+use log::*;
+use regex::{bytes, RegexBuilder};
+use rfd::AsyncFileDialog;
+
+use serial_monitor::{
+    ansi::{self, AnsiMode},
+    highlight::Highlighter,
+};
+
+use crate::{
+    api,
+    bigfile::{self, BigFile, HEX_WIDTH},
+    components::consoles::StyledLine,
+};
+
+/// Lines (or hex rows) shown at once.
+const PAGE: usize = 100;
+const WHEEL_LINES: usize = 3;
+const HIT_ID: &str = "file_viewer_hit";
+
+#[derive(Clone, Copy)]
+enum Jump {
+    Offset,
+    Line,
+    Time,
+}
+
+/// Browses captures too large to load into the consoles. The file is memory
+/// mapped, so only the page on screen is ever read.
+#[inline_props]
+pub fn FileViewer(cx: Scope, highlighter: UseRef<Highlighter>, ansi_mode: AnsiMode) -> Element {
+    let file = use_ref(cx, || None::<BigFile>);
+    let path = use_state(cx, || None::<PathBuf>);
+    let offset = use_state(cx, || 0u64);
+    let hex = use_state(cx, || false);
+    let target = use_state(cx, String::new);
+    let query = use_state(cx, String::new);
+    let is_regex = use_state(cx, || false);
+    let hit = use_state(cx, || None::<Range<u64>>);
+    let searching = use_state(cx, || false);
+    let status = use_state(cx, String::new);
+
+    // Keeps the top of the view on a line start in text mode and on a row in
+    // hex mode.
+    let align = move |data: &[u8], o: u64, hex: bool| {
+        if hex {
+            o - o % HEX_WIDTH as u64
+        } else {
+            bigfile::line_start(data, o)
+        }
+    };
+    let scroll = move |lines: isize| {
+        let f = file.read();
+        let Some(f) = f.as_ref() else {
+            return;
+        };
+        let data = f.data();
+        let o = **offset;
+        let n = lines.unsigned_abs();
+        let o = match (**hex, lines < 0) {
+            (true, true) => o.saturating_sub((n * HEX_WIDTH) as u64),
+            (true, false) => {
+                (o + (n * HEX_WIDTH) as u64).min(align(data, f.size().saturating_sub(1), true))
+            }
+            (false, true) => bigfile::lines_before(data, o, n),
+            (false, false) => {
+                let (_, next) = bigfile::lines_from(data, o, n);
+                if next < f.size() {
+                    next
+                } else {
+                    o
+                }
+            }
+        };
+        offset.set(o);
+    };
+    let to_end = move |_| {
+        if let Some(f) = file.read().as_ref() {
+            let data = f.data();
+            let last = f.size().saturating_sub(1);
+            offset.set(if **hex {
+                align(data, last, true).saturating_sub(((PAGE - 1) * HEX_WIDTH) as u64)
+            } else {
+                bigfile::lines_before(data, last, PAGE - 1)
+            });
+        }
+    };
+    let open = move |_| {
+        cx.spawn({
+            to_owned![file, path, offset, hit, status];
+            async move {
+                let Some(picked) = AsyncFileDialog::new().pick_file().await else {
+                    return;
+                };
+                let p = picked.path().to_owned();
+                let opened = match BigFile::open(&p) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        api::show_error_dialog("Failed to open file", &format!("{e}")).await;
+                        return;
+                    }
+                };
+                info!("Opened {} ({} bytes)", p.display(), opened.size());
+                let data = opened.shared();
+                file.set(Some(opened));
+                path.set(Some(p));
+                offset.set(0);
+                hit.set(None);
+                status.set(String::new());
+                let index = tokio::task::spawn_blocking({
+                    let data = data.clone();
+                    move || bigfile::build_index(&data)
+                })
+                .await
+                .unwrap();
+                // Another file may have been opened while this one was indexed.
+                if let Some(f) = file
+                    .write()
+                    .as_mut()
+                    .filter(|f| Arc::ptr_eq(&f.shared(), &data))
+                {
+                    f.set_index(index);
+                }
+            }
+        })
+    };
+    let jump = move |kind: Jump| {
+        let f = file.read();
+        let Some(f) = f.as_ref() else {
+            return;
+        };
+        let data = f.data();
+        let t = target.trim();
+        let res = match kind {
+            Jump::Offset => {
+                let parsed = match t.strip_prefix("0x") {
+                    Some(h) => u64::from_str_radix(h, 16),
+                    None => t.parse(),
+                };
+                parsed
+                    .map(|o| o.min(f.size().saturating_sub(1)))
+                    .map_err(|_| "Not an offset; use decimal or 0x hex".to_string())
+            }
+            Jump::Line => match (t.parse::<u64>(), f.index()) {
+                (Err(_), _) => Err("Not a line number".to_string()),
+                (_, None) => Err("Still indexing lines".to_string()),
+                (Ok(line), Some(index)) => {
+                    Ok(bigfile::line_offset(data, index, line.saturating_sub(1)))
+                }
+            },
+            Jump::Time => match bigfile::parse_time(t) {
+                None => Err("Not a time, e.g. 2024-01-31 12:00:00".to_string()),
+                Some(time) => bigfile::find_time(data, time)
+                    .ok_or_else(|| "No lines stamped at or after that time".to_string()),
+            },
+        };
+        match res {
+            Ok(o) => {
+                offset.set(align(data, o, **hex));
+                status.set(String::new());
+            }
+            Err(e) => status.set(e),
+        }
+    };
+    let search = move || {
+        if query.is_empty() || **searching {
+            return;
+        }
+        let pattern = if **is_regex {
+            query.to_string()
+        } else {
+            regex::escape(query)
+        };
+        let re = match bytes::RegexBuilder::new(&pattern)
+            .case_insensitive(!**is_regex)
+            .build()
+        {
+            Ok(re) => re,
+            Err(e) => {
+                status.set(format!("Invalid pattern: {e}"));
+                return;
+            }
+        };
+        let Some(data) = file.read().as_ref().map(|f| f.shared()) else {
+            return;
+        };
+        let from = match hit.get() {
+            Some(h) => h.start + 1,
+            None => **offset,
+        };
+        searching.set(true);
+        cx.spawn({
+            to_owned![offset, hex, hit, searching, status];
+            async move {
+                let found = tokio::task::spawn_blocking({
+                    let data = data.clone();
+                    move || bigfile::find(&data, &re, from)
+                })
+                .await
+                .unwrap();
+                searching.set(false);
+                match found {
+                    Some(range) => {
+                        let top = if *hex {
+                            range.start - range.start % HEX_WIDTH as u64
+                        } else {
+                            // A few lines of context above the hit keep it on
+                            // screen without scrolling the page.
+                            bigfile::lines_before(&data, range.start, 3)
+                        };
+                        offset.set(top);
+                        status.set(if range.start < from {
+                            "Wrapped to the start".to_string()
+                        } else {
+                            String::new()
+                        });
+                        hit.set(Some(range));
+                    }
+                    None => {
+                        status.set("No matches".to_string());
+                        hit.set(None);
+                    }
+                }
+            }
+        });
+    };
+
+    let f = file.read();
+    let text_regex = (!query.is_empty())
+        .then(|| {
+            let pattern = if **is_regex {
+                query.to_string()
+            } else {
+                regex::escape(query)
+            };
+            RegexBuilder::new(&pattern)
+                .case_insensitive(!**is_regex)
+                .build()
+                .ok()
+        })
+        .flatten();
+    let hit_range = hit.get().clone();
+    let hit_in = |start: u64, end: u64| {
+        hit_range
+            .as_ref()
+            .is_some_and(|h| start <= h.start && h.start <= end)
+    };
+
+    let (rows, lines, position) = match f.as_ref() {
+        None => (Vec::new(), Vec::new(), String::new()),
+        Some(f) => {
+            let data = f.data();
+            let o = **offset;
+            let line = f
+                .index()
+                .map(|index| format!(" · line {}", bigfile::line_number(data, index, o) + 1))
+                .unwrap_or_else(|| " · indexing lines…".to_string());
+            let position = format!("{o:#x} / {:#x}{line}", f.size());
+            if **hex {
+                let rows = bigfile::hex_rows(data, o, PAGE)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, row)| {
+                        let start = o + (i * HEX_WIDTH) as u64;
+                        (row, hit_in(start, start + HEX_WIDTH as u64))
+                    })
+                    .collect();
+                (rows, Vec::new(), position)
+            } else {
+                let (page, _) = bigfile::lines_from(data, o, PAGE);
+                let mut start = o;
+                let lines = page
+                    .into_iter()
+                    .map(|l| {
+                        let is_hit = hit_in(start, start + l.len() as u64);
+                        start += l.len() as u64 + 1;
+                        if data.get(start as usize - 1) != Some(&b'\n') {
+                            // A CRLF ending was stripped.
+                            start += 1;
+                        }
+                        let text = String::from_utf8_lossy(l);
+                        let line = ansi::parse(&text, *ansi_mode)
+                            .into_iter()
+                            .next()
+                            .unwrap_or_default();
+                        let marks = text_regex
+                            .as_ref()
+                            .map(|re| {
+                                let mut first = true;
+                                re.find_iter(&line.text)
+                                    .map(|m| {
+                                        let current = is_hit && std::mem::take(&mut first);
+                                        (m.range(), current)
+                                    })
+                                    .collect::<Vec<_>>()
+                            })
+                            .unwrap_or_default();
+                        (line, marks)
+                    })
+                    .collect();
+                (Vec::new(), lines, position)
+            }
+        }
+    };
+    let name = path
+        .as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| "No file open".to_string());
+    let is_open = f.is_some();
+    drop(f);
+
+    let toggle_class = |on: bool| {
+        if on {
+            "btn btn-secondary"
+        } else {
+            "btn btn-outline-secondary"
+        }
+    };
+
+    render! {
+        div {
+            class: "h-100 d-flex flex-column gap-1",
+            div {
+                class: "d-flex gap-2 align-items-center flex-wrap",
+                button {
+                    class: "btn btn-sm btn-outline-primary",
+                    onclick: open,
+                    "Open file"
+                }
+                div {
+                    class: "btn-group btn-group-sm",
+                    button {
+                        class: toggle_class(!**hex),
+                        onclick: move |_| {
+                            if let Some(f) = file.read().as_ref() {
+                                offset.set(align(f.data(), **offset, false));
+                            }
+                            hex.set(false);
+                        },
+                        "Text"
+                    }
+                    button {
+                        class: toggle_class(**hex),
+                        onclick: move |_| {
+                            offset.set(**offset - **offset % HEX_WIDTH as u64);
+                            hex.set(true);
+                        },
+                        "Hex"
+                    }
+                }
+                div {
+                    class: "input-group input-group-sm w-auto",
+                    input {
+                        class: "form-control",
+                        value: "{target}",
+                        placeholder: "Offset, line or time",
+                        spellcheck: "false",
+                        oninput: move |e| target.set(e.value.clone()),
+                    }
+                    button { class: "btn btn-outline-secondary", disabled: !is_open, onclick: move |_| jump(Jump::Offset), "Offset" }
+                    button { class: "btn btn-outline-secondary", disabled: !is_open, onclick: move |_| jump(Jump::Line), "Line" }
+                    button { class: "btn btn-outline-secondary", disabled: !is_open, onclick: move |_| jump(Jump::Time), "Time" }
+                }
+                div {
+                    class: "input-group input-group-sm w-auto flex-grow-1",
+                    input {
+                        class: "form-control",
+                        value: "{query}",
+                        placeholder: "Find",
+                        spellcheck: "false",
+                        oninput: move |e| {
+                            query.set(e.value.clone());
+                            hit.set(None);
+                        },
+                        onkeydown: move |e| {
+                            if e.key() == Key::Enter {
+                                search();
+                            }
+                        },
+                    }
+                    button {
+                        class: toggle_class(**is_regex),
+                        title: "Regular expression",
+                        onclick: move |_| {
+                            is_regex.set(!**is_regex);
+                            hit.set(None);
+                        },
+                        ".*"
+                    }
+                    button {
+                        class: "btn btn-outline-secondary",
+                        title: "Next match (Enter)",
+                        disabled: !is_open || **searching,
+                        onclick: move |_| search(),
+                        if **searching { "…" } else { "↓" }
+                    }
+                }
+            }
+            div {
+                class: "form-control w-100 flex-grow-1 overflow-hidden",
+                min_height: "1rem",
+                tabindex: "0",
+                font_size: "0.875rem",
+                white_space: "pre",
+                font_family: if **hex { "monospace" } else { "" },
+                onwheel: move |e| {
+                    let dy = e.delta().strip_units().y;
+                    if dy != 0.0 {
+                        scroll(if dy < 0.0 { -(WHEEL_LINES as isize) } else { WHEEL_LINES as isize });
+                    }
+                },
+                onkeydown: move |e| match e.key() {
+                    Key::PageUp => scroll(-(PAGE as isize)),
+                    Key::PageDown => scroll(PAGE as isize),
+                    Key::ArrowUp => scroll(-1),
+                    Key::ArrowDown => scroll(1),
+                    Key::Home => offset.set(0),
+                    _ => (),
+                },
+                rows.into_iter().enumerate().map(|(i, (row, is_hit))| rsx! {
+                    div {
+                        key: "{i}",
+                        id: if is_hit { HIT_ID } else { "" },
+                        class: if is_hit { "bg-warning-subtle" } else { "" },
+                        "{row}"
+                    }
+                })
+                lines.iter().enumerate().map(|(i, (line, marks))| rsx! {
+                    div {
+                        key: "{i}",
+                        min_height: "1.2em",
+                        StyledLine { line: line.clone(), highlighter: highlighter.clone(), marks: marks.clone(), hit_id: HIT_ID.to_string() }
+                    }
+                })
+            }
+            div {
+                class: "d-flex gap-2 align-items-center small",
+                div {
+                    class: "btn-group btn-group-sm",
+                    button { class: "btn btn-outline-secondary", title: "Start (Home)", onclick: move |_| offset.set(0), "⇤" }
+                    button { class: "btn btn-outline-secondary", title: "Page up", onclick: move |_| scroll(-(PAGE as isize)), "↑" }
+                    button { class: "btn btn-outline-secondary", title: "Page down", onclick: move |_| scroll(PAGE as isize), "↓" }
+                    button { class: "btn btn-outline-secondary", title: "End", onclick: to_end, "⇥" }
+                }
+                span { class: "text-truncate", title: "{name}", "{name}" }
+                span { class: "text-secondary text-nowrap", "{position}" }
+                span { class: "text-warning ms-auto text-nowrap", "{status}" }
+            }
+        }
+    }
+}
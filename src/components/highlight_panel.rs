@@ -0,0 +1,170 @@
+use dioxus::prelude::*;
+use log::*;
+
+use serial_monitor::highlight::{HighlightRule, HighlightRules, Highlighter};
+
+use crate::settings;
+
+/// Changes the rules without saving them, for edits still being typed or
+/// picked.
+fn edit(highlighter: &UseRef<Highlighter>, f: impl FnOnce(&mut Vec<HighlightRule>)) {
+    highlighter.with_mut(|h| {
+        let mut rules = h.rules().to_vec();
+        f(&mut rules);
+        h.set_rules(rules);
+    });
+}
+
+fn update(highlighter: &UseRef<Highlighter>, f: impl FnOnce(&mut Vec<HighlightRule>)) {
+    edit(highlighter, f);
+    save(highlighter);
+}
+
+fn save(highlighter: &UseRef<Highlighter>) {
+    if let Err(e) = settings::save_highlighter(&highlighter.read()) {
+        error!("Failed to save highlight rules: {:?}", e);
+    }
+}
+
+#[inline_props]
+pub fn HighlightPanel(cx: Scope, highlighter: UseRef<Highlighter>) -> Element {
+    let count = highlighter.read().rules().len();
+
+    render! {
+        div {
+            class: "h-100 d-flex flex-column gap-2 overflow-auto",
+            div {
+                class: "form-text",
+                "Matches are styled by the first enabled rule that covers them."
+            }
+            (0..count).map(|i| rsx! { RuleEditor { key: "{i}", index: i, highlighter: highlighter.clone() } })
+            div {
+                class: "d-flex gap-2",
+                button {
+                    class: "btn btn-outline-primary btn-sm flex-grow-1",
+                    onclick: move |_| update(highlighter, |r| r.push(HighlightRule {
+                        pattern: String::new(),
+                        foreground: Some("#20c997".to_string()),
+                        background: None,
+                        bold: false,
+                        enabled: true,
+                    })),
+                    "Add rule"
+                }
+                button {
+                    class: "btn btn-outline-secondary btn-sm",
+                    onclick: move |_| update(highlighter, |r| *r = HighlightRules::default().0),
+                    "Reset defaults"
+                }
+            }
+        }
+    }
+}
+
+#[inline_props]
+fn RuleEditor(cx: Scope, index: usize, highlighter: UseRef<Highlighter>) -> Element {
+    let index = *index;
+    let (rule, valid) = match highlighter.read().rules().get(index) {
+        Some(r) => (r.clone(), highlighter.read().is_valid(index)),
+        None => return None,
+    };
+    let pattern_class = if valid {
+        "form-control form-control-sm font-monospace"
+    } else {
+        "form-control form-control-sm font-monospace is-invalid"
+    };
+    let style = rule.style();
+    let fg = rule.foreground.clone().unwrap_or("#ffffff".to_string());
+    let bg = rule.background.clone().unwrap_or("#000000".to_string());
+
+    render! {
+        div {
+            class: "card",
+            div {
+                class: "card-body p-2 d-flex flex-column gap-1",
+                div {
+                    class: "d-flex gap-2 align-items-center",
+                    input {
+                        class: "form-check-input mt-0",
+                        r#type: "checkbox",
+                        checked: "{rule.enabled}",
+                        onchange: move |e| update(highlighter, |r| r[index].enabled = e.value == "true"),
+                    }
+                    input {
+                        class: "{pattern_class}",
+                        value: "{rule.pattern}",
+                        placeholder: "regex",
+                        spellcheck: "false",
+                        oninput: move |e| edit(highlighter, |r| r[index].pattern = e.value.clone()),
+                        onchange: move |_| save(highlighter),
+                    }
+                    button {
+                        class: "btn btn-outline-danger btn-sm",
+                        onclick: move |_| update(highlighter, |r| { r.remove(index); }),
+                        "✕"
+                    }
+                }
+                div {
+                    class: "d-flex gap-3 align-items-center",
+                    ColorOption {
+                        label: "Text",
+                        enabled: rule.foreground.is_some(),
+                        value: fg,
+                        oninput: move |c: Option<String>| edit(highlighter, |r| r[index].foreground = c),
+                        onchange: move |c: Option<String>| update(highlighter, |r| r[index].foreground = c),
+                    }
+                    ColorOption {
+                        label: "Background",
+                        enabled: rule.background.is_some(),
+                        value: bg,
+                        oninput: move |c: Option<String>| edit(highlighter, |r| r[index].background = c),
+                        onchange: move |c: Option<String>| update(highlighter, |r| r[index].background = c),
+                    }
+                    label {
+                        class: "form-check-label d-flex gap-1 align-items-center",
+                        input {
+                            class: "form-check-input mt-0",
+                            r#type: "checkbox",
+                            checked: "{rule.bold}",
+                            onchange: move |e| update(highlighter, |r| r[index].bold = e.value == "true"),
+                        }
+                        "Bold"
+                    }
+                    span { class: "ms-auto font-monospace", style: "{style}", "Sample" }
+                }
+            }
+        }
+    }
+}
+
+#[inline_props]
+fn ColorOption<'a>(
+    cx: Scope<'a>,
+    label: &'static str,
+    enabled: bool,
+    value: String,
+    oninput: EventHandler<'a, Option<String>>,
+    onchange: EventHandler<'a, Option<String>>,
+) -> Element<'a> {
+    let enabled = *enabled;
+    render! {
+        label {
+            class: "form-check-label d-flex gap-1 align-items-center",
+            input {
+                class: "form-check-input mt-0",
+                r#type: "checkbox",
+                checked: "{enabled}",
+                onchange: move |e| onchange.call((e.value == "true").then(|| value.clone())),
+            }
+            input {
+                class: "form-control form-control-color form-control-sm",
+                r#type: "color",
+                value: "{value}",
+                disabled: !enabled,
+                oninput: move |e| oninput.call(Some(e.value.clone())),
+                onchange: move |e| onchange.call(Some(e.value.clone())),
+            }
+            "{label}"
+        }
+    }
+}
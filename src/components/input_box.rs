@@ -4,7 +4,12 @@ use dioxus::{
 };
 use log::*;
 
-use crate::api::{self, Connection};
+use serial_monitor::{connection::Connection, export::Format, highlight::Highlighter};
+
+use crate::{
+    api,
+    terminal::{self, BackspaceKey},
+};
 
 #[inline_props]
 pub fn InputBox(
@@ -12,12 +17,18 @@ pub fn InputBox(
     user_buffer: UseRef<Vec<String>>,
     port_buffer: UseRef<Vec<String>>,
     connection: UseRef<Connection>,
+    highlighter: UseRef<Highlighter>,
 ) -> Element {
     let inp = use_state(cx, || String::new());
+    let char_mode = use_state(cx, || false);
+    let local_echo = use_state(cx, || false);
+    let backspace = use_state(cx, BackspaceKey::default);
 
-    let write = |s: &str| {
-        let s = format!("{s}\n");
+    let send = move |s: String, echo: bool| {
         user_buffer.with_mut(|b| b.push(s.clone()));
+        if echo {
+            port_buffer.with_mut(|b| b.push(s.clone()));
+        }
         cx.spawn({
             to_owned![connection];
             async move {
@@ -27,40 +38,87 @@ pub fn InputBox(
             }
         })
     };
+    let write = move |s: &str| send(format!("{s}\n"), false);
 
     render! {
         div {
             class: "d-flex gap-2",
             div {
                 class: "input-group",
-                input {
-                    value: "{inp}",
-                    class: "form-control bg-gradient",
-                    spellcheck: "false",
-                    oninput: move |event| {
-                       inp.set(event.value.clone());
-                    },
-                    onkeypress: move |event| {
-                        if !inp.is_empty() && !event.modifiers().contains(Modifiers::SHIFT) && event.key() == Key::Enter {
-                            write(&inp);
-                            inp.set(String::new());
+                if **char_mode {
+                    rsx! {
+                        input {
+                            class: "form-control bg-gradient",
+                            readonly: true,
+                            placeholder: "Keys are sent as typed",
+                            prevent_default: "onkeydown",
+                            onkeydown: move |event| {
+                                let key = terminal::encode_key(&event.key(), event.modifiers(), false, **backspace);
+                                if let Some(s) = key {
+                                    send(s, **local_echo);
+                                }
+                            },
+                        }
+                        select {
+                            class: "form-select flex-grow-0 w-auto",
+                            title: "Backspace sends",
+                            value: if **backspace == BackspaceKey::Bs { "bs" } else { "del" },
+                            onchange: move |e| backspace.set(if e.value == "bs" { BackspaceKey::Bs } else { BackspaceKey::Del }),
+                            option { value: "del", "⌫ DEL" }
+                            option { value: "bs", "⌫ BS" }
+                        }
+                        div {
+                            class: "input-group-text gap-1",
+                            input {
+                                class: "form-check-input mt-0",
+                                r#type: "checkbox",
+                                id: "local_echo",
+                                checked: "{local_echo}",
+                                onchange: move |e| local_echo.set(e.value == "true"),
+                            }
+                            label { class: "form-check-label", r#for: "local_echo", "Echo" }
+                        }
+                    }
+                } else {
+                    rsx! {
+                        input {
+                            value: "{inp}",
+                            class: "form-control bg-gradient",
+                            spellcheck: "false",
+                            oninput: move |event| {
+                               inp.set(event.value.clone());
+                            },
+                            onkeypress: move |event| {
+                                if !inp.is_empty() && !event.modifiers().contains(Modifiers::SHIFT) && event.key() == Key::Enter {
+                                    write(&inp);
+                                    inp.set(String::new());
+                                }
+                            }
+                        }
+                        button {
+                            class: "btn btn-primary bg-gradient",
+                            onclick: move |_| {
+                                if !inp.is_empty() {
+                                    write(&inp);
+                                    inp.set(String::new());
+                                }
+                            },
+                            "Send"
                         }
                     }
                 }
                 button {
-                    class: "btn btn-primary bg-gradient",
-                    onclick: move |_| {
-                        if !inp.is_empty() {
-                            write(&inp);
-                            inp.set(String::new());
-                        }
-                    },
-                    "Send"
+                    class: if **char_mode { "btn btn-secondary" } else { "btn btn-outline-secondary" },
+                    title: "Send each key as it is typed",
+                    onclick: move |_| char_mode.set(!**char_mode),
+                    "Char"
                 }
             },
             DownloadButton {
                 user_buffer: user_buffer.clone(),
                 port_buffer: port_buffer.clone(),
+                connection: connection.clone(),
+                highlighter: highlighter.clone(),
                 titles: vec!["user".to_string(), connection.read().get_name().to_string()]
             }
         }
@@ -72,16 +130,22 @@ fn DownloadButton(
     cx: Scope,
     user_buffer: UseRef<Vec<String>>,
     port_buffer: UseRef<Vec<String>>,
+    connection: UseRef<Connection>,
+    highlighter: UseRef<Highlighter>,
     titles: Vec<String>,
 ) -> Element {
     let is_downloading = use_state(cx, || false);
+    let format = use_state(cx, Format::default);
     let trigger_download = |_| {
-        let content = vec![user_buffer.read().clone(), port_buffer.read().clone()];
+        let buffers = [user_buffer.clone(), port_buffer.clone()];
+        let rules = highlighter.read().rules().to_vec();
+        let metadata = connection.read().metadata();
+        let format = **format;
         cx.spawn({
-            to_owned![titles, is_downloading];
+            to_owned![connection, titles, is_downloading];
             async move {
                 is_downloading.set(true);
-                api::download(titles, content).await;
+                api::download(format, connection, rules, metadata, titles, buffers).await;
                 is_downloading.set(false);
             }
         })
@@ -90,10 +154,24 @@ fn DownloadButton(
     render! {
         if !*is_downloading.get() {
             rsx! {
-                button {
-                    class: "btn btn-primary bg-gradient",
-                    onclick: trigger_download,
-                    "Download"
+                div {
+                    class: "input-group w-auto flex-nowrap",
+                    select {
+                        class: "form-select",
+                        title: "Download format",
+                        value: format.name(),
+                        onchange: move |e| {
+                            if let Some(f) = Format::ALL.iter().find(|f| f.name() == e.value) {
+                                format.set(*f);
+                            }
+                        },
+                        Format::ALL.iter().map(|f| rsx! { option { value: f.name(), f.name() } })
+                    }
+                    button {
+                        class: "btn btn-primary bg-gradient",
+                        onclick: trigger_download,
+                        "Download"
+                    }
                 }
             }
         } else {
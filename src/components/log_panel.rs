@@ -0,0 +1,180 @@
+use dioxus::prelude::*;
+use log::*;
+use rfd::AsyncFileDialog;
+
+use serial_monitor::{connection::Connection, import};
+
+use crate::api::{self, LoadedLog, ReplayTarget, Viewer};
+
+const SPEEDS: [f64; 6] = [0.5, 1.0, 2.0, 5.0, 10.0, 100.0];
+
+#[inline_props]
+pub fn LogPanel(
+    cx: Scope,
+    log: UseRef<Option<LoadedLog>>,
+    connection: UseRef<Connection>,
+    viewer: Viewer,
+) -> Element {
+    let target = use_state(cx, || ReplayTarget::View);
+    let speed = use_state(cx, || 1.0f64);
+    let loading = use_state(cx, || false);
+
+    let stop = move || {
+        if let Some(id) = log.write().as_mut().and_then(|l| l.task.take()) {
+            cx.remove_future(id);
+        }
+    };
+    let open = move |_| {
+        stop();
+        cx.spawn({
+            to_owned![log, loading];
+            async move {
+                let Some(file) = AsyncFileDialog::new()
+                    .add_filter("Exported logs", &["csv", "jsonl"])
+                    .pick_file()
+                    .await
+                else {
+                    return;
+                };
+                let path = file.path().to_owned();
+                loading.set(true);
+                let res = tokio::task::spawn_blocking({
+                    let path = path.clone();
+                    move || import::load(&path)
+                })
+                .await
+                .unwrap();
+                loading.set(false);
+                match res {
+                    Ok(capture) => {
+                        info!("Loaded {} records from {:?}", capture.records.len(), path);
+                        log.set(Some(LoadedLog {
+                            path,
+                            capture,
+                            position: 0,
+                            task: None,
+                        }));
+                    }
+                    Err(e) => api::show_error_dialog("Failed to open log", &format!("{e:#}")).await,
+                }
+            }
+        })
+    };
+    let start = move |_| {
+        stop();
+        if let Some(l) = log.write().as_mut() {
+            l.position = 0;
+        }
+        let id = cx.spawn_forever(api::replay(
+            log.clone(),
+            **target,
+            **speed,
+            connection.clone(),
+            viewer.clone(),
+        ));
+        if let Some(l) = log.write().as_mut() {
+            l.task = Some(id);
+        }
+    };
+
+    let l = log.read();
+    let info = l.as_ref().map(|l| {
+        let name = l
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        (
+            name,
+            l.capture.records.len(),
+            format!("{:.1}", l.capture.duration()),
+            l.capture.metadata.clone(),
+            l.position,
+            l.task.is_some(),
+        )
+    });
+    drop(l);
+
+    render! {
+        div {
+            class: "h-100 d-flex flex-column gap-2 overflow-auto",
+            div {
+                class: "d-flex gap-2 align-items-center",
+                button {
+                    class: "btn btn-sm btn-outline-primary",
+                    disabled: **loading,
+                    onclick: open,
+                    if **loading { "Opening..." } else { "Open log" }
+                }
+                span { class: "form-text mt-0", "CSV event logs and JSON Lines exports" }
+            }
+            match info {
+                None => rsx! { Fragment {} },
+                Some((name, count, duration, metadata, position, running)) => rsx! {
+                    div {
+                        class: "card",
+                        div {
+                            class: "card-body p-2 small",
+                            div { class: "fw-bold text-break", "{name}" }
+                            div { class: "text-secondary", "{count} records over {duration} s" }
+                            table {
+                                class: "table table-sm table-borderless mb-0 mt-1",
+                                tbody {
+                                    metadata.into_iter().map(|(k, v)| rsx! {
+                                        tr { key: "{k}", td { class: "text-secondary py-0", "{k}" } td { class: "py-0 text-break", "{v}" } }
+                                    })
+                                }
+                            }
+                        }
+                    }
+                    button {
+                        class: "btn btn-sm btn-outline-secondary",
+                        title: "Replace the consoles, plot and dashboard with the whole log",
+                        onclick: move |_| {
+                            stop();
+                            if let Some(l) = log.read().as_ref() {
+                                viewer.show(&l.capture);
+                            }
+                        },
+                        "Show in console"
+                    }
+                    div {
+                        class: "input-group input-group-sm",
+                        span { class: "input-group-text", "Replay" }
+                        select {
+                            class: "form-select",
+                            value: if **target == ReplayTarget::View { "view" } else { "port" },
+                            onchange: move |e| target.set(if e.value == "port" { ReplayTarget::Port } else { ReplayTarget::View }),
+                            option { value: "view", "into the view" }
+                            option { value: "port", "received data to the port" }
+                        }
+                        select {
+                            class: "form-select flex-grow-0 w-auto",
+                            value: "{speed}",
+                            onchange: move |e| {
+                                if let Ok(s) = e.value.parse() {
+                                    speed.set(s);
+                                }
+                            },
+                            SPEEDS.iter().map(|s| rsx! { option { value: "{s}", "{s}×" } })
+                        }
+                        if running {
+                            rsx! { button { class: "btn btn-warning", onclick: move |_| stop(), "Stop" } }
+                        } else {
+                            rsx! { button { class: "btn btn-primary", onclick: start, "Start" } }
+                        }
+                    }
+                    div {
+                        class: "progress",
+                        role: "progressbar",
+                        div {
+                            class: "progress-bar",
+                            width: "{position * 100 / count.max(1)}%",
+                        }
+                    }
+                    div { class: "form-text mt-0", "{position} of {count} records replayed" }
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use dioxus::prelude::*;
+
+use crate::bridge::{BridgeSettings, BridgeStatus, Policy};
+
+#[inline_props]
+pub fn NetworkPanel(
+    cx: Scope,
+    bridge: Coroutine<Option<BridgeSettings>>,
+    bridge_settings: UseRef<BridgeSettings>,
+    bridge_status: UseRef<BridgeStatus>,
+    telnet: Coroutine<Option<BridgeSettings>>,
+    telnet_settings: UseRef<BridgeSettings>,
+    telnet_status: UseRef<BridgeStatus>,
+    websocket: Coroutine<Option<BridgeSettings>>,
+    websocket_settings: UseRef<BridgeSettings>,
+    websocket_status: UseRef<BridgeStatus>,
+) -> Element {
+    render! {
+        div {
+            class: "h-100 d-flex flex-column gap-2 overflow-auto",
+            BridgeSection {
+                title: "TCP bridge",
+                help: "Clients receive everything the port sends, as raw bytes.",
+                bridge: bridge.clone(),
+                settings: bridge_settings.clone(),
+                status: bridge_status.clone(),
+            }
+            BridgeSection {
+                title: "Telnet",
+                help: "Clients are put in character mode and see the device's echo. Their BREAK is sent to the port.",
+                bridge: telnet.clone(),
+                settings: telnet_settings.clone(),
+                status: telnet_status.clone(),
+            }
+            BridgeSection {
+                title: "WebSocket",
+                help: "Every record sent or received goes out as JSON with timestamp, direction, port, base64 and text. Clients write with {{\"send\": text}}, {{\"base64\": data}} or binary messages. Browsers may only connect from pages on this machine.",
+                bridge: websocket.clone(),
+                settings: websocket_settings.clone(),
+                status: websocket_status.clone(),
+            }
+            div {
+                class: "form-text mt-0",
+                "Listen on 0.0.0.0 to accept clients from other machines. They only watch unless allowed to write."
+            }
+        }
+    }
+}
+
+#[inline_props]
+fn BridgeSection(
+    cx: Scope,
+    title: &'static str,
+    help: &'static str,
+    bridge: Coroutine<Option<BridgeSettings>>,
+    settings: UseRef<BridgeSettings>,
+    status: UseRef<BridgeStatus>,
+) -> Element {
+    let current = settings.read().clone();
+    let status = status.read().clone();
+    let running = status.listening.is_some();
+    let current = &current;
+
+    render! {
+        h6 { class: "mb-0", "{title}" }
+        div {
+            class: "input-group input-group-sm",
+            span { class: "input-group-text", "Listen on" }
+            input {
+                class: "form-control font-monospace",
+                value: "{current.address}",
+                spellcheck: "false",
+                disabled: running,
+                oninput: move |e| settings.with_mut(|s| s.address = e.value.clone()),
+            }
+            select {
+                class: "form-select flex-grow-0 w-auto",
+                title: "Who may write to the port",
+                value: current.policy.name(),
+                disabled: running,
+                onchange: move |e| {
+                    if let Some(p) = Policy::ALL.iter().find(|p| p.name() == e.value) {
+                        settings.with_mut(|s| s.policy = *p);
+                    }
+                },
+                Policy::ALL.iter().map(|p| rsx! { option { value: p.name(), p.name() } })
+            }
+            if running {
+                rsx! {
+                    button {
+                        class: "btn btn-danger",
+                        onclick: move |_| bridge.send(None),
+                        "Stop"
+                    }
+                }
+            } else {
+                rsx! {
+                    button {
+                        class: "btn btn-primary",
+                        onclick: move |_| bridge.send(Some(settings.read().clone())),
+                        "Start"
+                    }
+                }
+            }
+        }
+        label {
+            class: "form-check small mb-0",
+            input {
+                class: "form-check-input",
+                r#type: "checkbox",
+                checked: "{current.remote_writes}",
+                disabled: running,
+                onchange: move |e| settings.with_mut(|s| s.remote_writes = e.value == "true"),
+            }
+            span { class: "form-check-label", "Let clients on other machines write" }
+        }
+        if let Some(e) = &status.error {
+            rsx! { div { class: "alert alert-danger py-1 px-2 mb-0 small", "{e}" } }
+        }
+        if let Some(addr) = status.listening {
+            rsx! {
+                div {
+                    class: "small",
+                    "Listening on {addr}, "
+                    match status.clients.len() {
+                        0 => "no clients".to_string(),
+                        1 => "1 client".to_string(),
+                        n => format!("{n} clients"),
+                    }
+                }
+                ul {
+                    class: "list-group list-group-flush small",
+                    status.clients.iter().map(|(id, addr)| {
+                        let writes = status.may_write(current, *id);
+                        rsx! {
+                            li {
+                                key: "{id}",
+                                class: "list-group-item d-flex justify-content-between py-1",
+                                span { class: "font-monospace", "{addr}" }
+                                span {
+                                    class: if writes { "badge text-bg-primary" } else { "badge text-bg-secondary" },
+                                    if writes { "writer" } else { "observer" }
+                                }
+                            }
+                        }
+                    })
+                }
+            }
+        }
+        div { class: "form-text mt-0", "{help}" }
+    }
+}
@@ -0,0 +1,62 @@
+use dioxus::prelude::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Panel {
+    Script,
+    Responder,
+    Highlight,
+    Plot,
+    Dashboard,
+    Recording,
+    Network,
+    Log,
+}
+
+impl Panel {
+    pub const ALL: [Panel; 8] = [
+        Panel::Script,
+        Panel::Responder,
+        Panel::Highlight,
+        Panel::Plot,
+        Panel::Dashboard,
+        Panel::Recording,
+        Panel::Network,
+        Panel::Log,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Panel::Script => "Script",
+            Panel::Responder => "Auto-reply",
+            Panel::Highlight => "Highlight",
+            Panel::Plot => "Plot",
+            Panel::Dashboard => "Dashboard",
+            Panel::Recording => "Recording",
+            Panel::Network => "Network",
+            Panel::Log => "Log file",
+        }
+    }
+}
+
+#[inline_props]
+pub fn PanelSelector(cx: Scope, panel: UseState<Option<Panel>>) -> Element {
+    render! {
+        div {
+            class: "btn-group btn-group-sm",
+            role: "group",
+            Panel::ALL.iter().map(|&p| {
+                let active = **panel == Some(p);
+                rsx! {
+                    button {
+                        key: "{p.title()}",
+                        class: if active { "btn btn-secondary" } else { "btn btn-outline-secondary" },
+                        onclick: move |_| {
+                            panel.set(if active { None } else { Some(p) });
+                        },
+                        p.title()
+                    }
+                }
+            })
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use dioxus::prelude::*;
+use tokio::time::{interval, Duration};
+
+use serial_monitor::plot::{self, Plot};
+
+use crate::api;
+
+const REFRESH_FREQ: Duration = Duration::from_millis(100);
+const WINDOWS: [f64; 6] = [5.0, 10.0, 30.0, 60.0, 300.0, 0.0];
+const MIN_WINDOW: f64 = 0.5;
+const MAX_WINDOW: f64 = 3600.0;
+const ZOOM_STEP: f64 = 1.5;
+
+fn window_name(w: f64) -> String {
+    if w > 0.0 {
+        format!("{w} s")
+    } else {
+        "All".to_string()
+    }
+}
+
+#[inline_props]
+pub fn PlotPanel(cx: Scope, plot: UseRef<Plot>) -> Element {
+    let window = use_state(cx, || 10.0f64);
+    let paused = use_state(cx, || None::<f64>);
+
+    let update = cx.schedule_update();
+    use_future(cx, (), |_| async move {
+        let mut interval = interval(REFRESH_FREQ);
+        loop {
+            interval.tick().await;
+            update();
+        }
+    });
+
+    let zoom = move |zoom_in: bool| {
+        let w = if **window > 0.0 { **window } else { 60.0 };
+        let w = if zoom_in {
+            w / ZOOM_STEP
+        } else {
+            w * ZOOM_STEP
+        };
+        window.set(w.clamp(MIN_WINDOW, MAX_WINDOW));
+    };
+
+    let p = plot.read();
+    let end = paused.unwrap_or_else(|| p.now());
+    let start = if **window > 0.0 {
+        end - **window
+    } else {
+        p.series()
+            .iter()
+            .filter_map(|s| s.points.front())
+            .map(|(t, _)| *t)
+            .fold(end, f64::min)
+    };
+    let start = start.min(end - MIN_WINDOW);
+    let svg = plot::render_svg(&p, start, end);
+    let legend = p
+        .series()
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let last = s
+                .points
+                .back()
+                .map(|(_, v)| format!("{v}"))
+                .unwrap_or_default();
+            (i, s.name.clone(), s.visible, last)
+        })
+        .collect::<Vec<_>>();
+    drop(p);
+
+    render! {
+        div {
+            class: "h-100 d-flex flex-column gap-2 overflow-auto",
+            div {
+                class: "d-flex gap-2 align-items-center",
+                select {
+                    class: "form-select form-select-sm w-auto",
+                    value: "{window}",
+                    onchange: move |e| {
+                        if let Ok(w) = e.value.parse() {
+                            window.set(w);
+                        }
+                    },
+                    if !WINDOWS.contains(window) {
+                        rsx! { option { value: "{window}", window_name(**window) } }
+                    }
+                    WINDOWS.iter().map(|w| rsx! { option { value: "{w}", window_name(*w) } })
+                }
+                div {
+                    class: "btn-group btn-group-sm",
+                    button { class: "btn btn-outline-secondary", title: "Zoom in", onclick: move |_| zoom(true), "+" }
+                    button { class: "btn btn-outline-secondary", title: "Zoom out", onclick: move |_| zoom(false), "−" }
+                }
+                button {
+                    class: if paused.is_some() { "btn btn-sm btn-warning" } else { "btn btn-sm btn-outline-secondary" },
+                    onclick: move |_| paused.set(match **paused {
+                        Some(_) => None,
+                        None => Some(plot.read().now()),
+                    }),
+                    if paused.is_some() { "Resume" } else { "Pause" }
+                }
+                div {
+                    class: "btn-group btn-group-sm ms-auto",
+                    button {
+                        class: "btn btn-outline-secondary",
+                        title: "Export the current view as SVG",
+                        onclick: move |_| {
+                            let svg = plot::export_svg(&plot.read(), start, end);
+                            cx.spawn(api::export("SVG", "svg", "plot.svg", svg));
+                        },
+                        "SVG"
+                    }
+                    button {
+                        class: "btn btn-outline-secondary",
+                        title: "Export the series in view as CSV",
+                        onclick: move |_| {
+                            match plot::export_csv(&plot.read(), start, end) {
+                                Ok(csv) => cx.spawn(api::export("CSV", "csv", "plot.csv", csv)),
+                                Err(e) => cx.spawn(async move {
+                                    api::show_error_dialog("Failed to export CSV", &format!("{e}")).await;
+                                }),
+                            }
+                        },
+                        "CSV"
+                    }
+                }
+                button {
+                    class: "btn btn-sm btn-outline-danger",
+                    onclick: move |_| {
+                        plot.with_mut(|p| p.clear());
+                        paused.set(None);
+                    },
+                    "Clear"
+                }
+            }
+            div {
+                class: "rounded overflow-hidden",
+                onwheel: move |e| zoom(e.delta().strip_units().y < 0.0),
+                dangerous_inner_html: "{svg}",
+            }
+            div {
+                class: "d-flex flex-wrap gap-1",
+                if legend.is_empty() {
+                    rsx! {
+                        span {
+                            class: "form-text",
+                            "Waiting for numbers, e.g. \"1.5 2.7\" or \"temp:23.4 hum:40\"."
+                        }
+                    }
+                }
+                legend.into_iter().map(|(i, name, visible, last)| rsx! {
+                    button {
+                        key: "{name}",
+                        class: if visible { "btn btn-sm btn-outline-light" } else { "btn btn-sm btn-outline-secondary opacity-50" },
+                        onclick: move |_| plot.with_mut(|p| p.toggle(i)),
+                        span { color: plot::color(i), "■ " }
+                        "{name} "
+                        span { class: "font-monospace", "{last}" }
+                    }
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use dioxus::prelude::*;
+use log::*;
+use rfd::AsyncFileDialog;
+
+use serial_monitor::{
+    export::Format,
+    recorder::{Recorder, RecordingSettings},
+};
+
+use crate::settings;
+
+#[inline_props]
+pub fn RecordingPanel(
+    cx: Scope,
+    recording: UseRef<RecordingSettings>,
+    recorder: UseRef<Option<Recorder>>,
+) -> Element {
+    let update = move |f: &dyn Fn(&mut RecordingSettings)| {
+        recording.with_mut(|r| {
+            f(r);
+            if let Err(e) = settings::save_recording(r) {
+                error!("Failed to save recording settings: {:?}", e);
+            }
+        });
+    };
+    let browse = move |_| {
+        cx.spawn({
+            to_owned![recording];
+            async move {
+                if let Some(dir) = AsyncFileDialog::new().pick_folder().await {
+                    recording.with_mut(|r| {
+                        r.directory = Some(dir.path().to_owned());
+                        if let Err(e) = settings::save_recording(r) {
+                            error!("Failed to save recording settings: {:?}", e);
+                        }
+                    });
+                }
+            }
+        })
+    };
+
+    let settings = recording.read().clone();
+    let directory = settings
+        .directory()
+        .map(|d| d.display().to_string())
+        .unwrap_or_default();
+    let example = settings.file_name("/dev/ttyUSB0", 1);
+    let max_size = settings
+        .max_size_mb
+        .map(|v| v.to_string())
+        .unwrap_or_default();
+    let max_minutes = settings
+        .max_minutes
+        .map(|v| v.to_string())
+        .unwrap_or_default();
+    let current = recorder
+        .read()
+        .as_ref()
+        .and_then(|r| r.path())
+        .map(|p| p.display().to_string());
+
+    render! {
+        div {
+            class: "h-100 d-flex flex-column gap-2 overflow-auto",
+            if let Some(path) = current {
+                rsx! { div { class: "alert alert-danger py-1 px-2 mb-0 small text-break", "Recording to {path}" } }
+            }
+            div {
+                class: "input-group input-group-sm",
+                span { class: "input-group-text", "Folder" }
+                input { class: "form-control", readonly: true, value: "{directory}" }
+                button { class: "btn btn-outline-secondary", onclick: browse, "Browse" }
+            }
+            div {
+                class: "input-group input-group-sm",
+                span { class: "input-group-text", "File name" }
+                input {
+                    class: "form-control font-monospace",
+                    value: "{settings.template}",
+                    spellcheck: "false",
+                    oninput: move |e| update(&|r| r.template = e.value.clone()),
+                }
+            }
+            div { class: "form-text mt-0", "{{port}}, {{date}}, {{time}} and {{n}} are replaced, e.g. {example}" }
+            div {
+                class: "input-group input-group-sm",
+                span { class: "input-group-text", "Format" }
+                select {
+                    class: "form-select",
+                    value: settings.format.name(),
+                    onchange: move |e| {
+                        if let Some(f) = Format::ALL.iter().find(|f| f.name() == e.value) {
+                            update(&|r| r.format = *f);
+                        }
+                    },
+                    Format::ALL
+                        .iter()
+                        .filter(|f| **f != Format::CsvColumns)
+                        .map(|f| rsx! { option { value: f.name(), f.name() } })
+                }
+            }
+            div {
+                class: "d-flex gap-2",
+                div {
+                    class: "input-group input-group-sm",
+                    span { class: "input-group-text", "Rotate at" }
+                    input {
+                        class: "form-control",
+                        r#type: "number",
+                        min: "1",
+                        placeholder: "never",
+                        value: "{max_size}",
+                        onchange: move |e| update(&|r| r.max_size_mb = e.value.parse().ok().filter(|v| *v > 0)),
+                    }
+                    span { class: "input-group-text", "MB" }
+                }
+                div {
+                    class: "input-group input-group-sm",
+                    span { class: "input-group-text", "or" }
+                    input {
+                        class: "form-control",
+                        r#type: "number",
+                        min: "1",
+                        placeholder: "never",
+                        value: "{max_minutes}",
+                        onchange: move |e| update(&|r| r.max_minutes = e.value.parse().ok().filter(|v| *v > 0)),
+                    }
+                    span { class: "input-group-text", "min" }
+                }
+            }
+            div {
+                class: "form-check",
+                input {
+                    class: "form-check-input",
+                    r#type: "checkbox",
+                    id: "recording_gzip",
+                    checked: "{settings.gzip}",
+                    onchange: move |e| update(&|r| r.gzip = e.value == "true"),
+                }
+                label { class: "form-check-label", r#for: "recording_gzip", "Gzip rotated files" }
+            }
+            div { class: "form-text", "Changes apply to the next recording." }
+        }
+    }
+}
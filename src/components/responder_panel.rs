@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use dioxus::prelude::*;
+
+use crate::responder::{Responder, Rule};
+
+#[inline_props]
+pub fn ResponderPanel(cx: Scope, responder: UseRef<Responder>) -> Element {
+    let count = responder.read().entries().len();
+
+    render! {
+        div {
+            class: "h-100 d-flex flex-column gap-2 overflow-auto",
+            div {
+                class: "form-text",
+                "Rules are checked in order against each received frame; the first match replies. "
+                "Use $1 or ${{name}} for capture groups and \\r, \\n, \\xHH for control characters."
+            }
+            (0..count).map(|i| rsx! { RuleEditor { key: "{i}", index: i, responder: responder.clone() } })
+            button {
+                class: "btn btn-outline-primary btn-sm",
+                onclick: move |_| responder.with_mut(|r| r.push(Rule {
+                    name: format!("rule {}", count + 1),
+                    ..Default::default()
+                })),
+                "Add rule"
+            }
+        }
+    }
+}
+
+#[inline_props]
+fn RuleEditor(cx: Scope, index: usize, responder: UseRef<Responder>) -> Element {
+    let index = *index;
+    let (rule, valid, fired) = match responder.read().entries().get(index) {
+        Some(e) => (e.rule().clone(), e.is_valid(), e.has_fired()),
+        None => return None,
+    };
+    let pattern_class = if valid {
+        "form-control form-control-sm font-monospace"
+    } else {
+        "form-control form-control-sm font-monospace is-invalid"
+    };
+    let delay = rule.delay.as_millis();
+
+    render! {
+        div {
+            class: "card",
+            div {
+                class: "card-body p-2 d-flex flex-column gap-1",
+                div {
+                    class: "d-flex gap-2 align-items-center",
+                    input {
+                        class: "form-check-input mt-0",
+                        r#type: "checkbox",
+                        checked: "{rule.enabled}",
+                        onchange: move |e| responder.with_mut(|r| r.edit(index, |rule| rule.enabled = e.value == "true")),
+                    }
+                    input {
+                        class: "form-control form-control-sm",
+                        value: "{rule.name}",
+                        placeholder: "name",
+                        oninput: move |e| responder.with_mut(|r| r.edit(index, |rule| rule.name = e.value.clone())),
+                    }
+                    if fired && !rule.repeat {
+                        rsx! {
+                            button {
+                                class: "btn btn-outline-warning btn-sm text-nowrap",
+                                onclick: move |_| responder.with_mut(|r| r.rearm(index)),
+                                "Re-arm"
+                            }
+                        }
+                    }
+                    button {
+                        class: "btn btn-outline-danger btn-sm",
+                        onclick: move |_| responder.with_mut(|r| r.remove(index)),
+                        "✕"
+                    }
+                }
+                input {
+                    class: "{pattern_class}",
+                    value: "{rule.pattern}",
+                    placeholder: "regex, e.g. login:\\s*$",
+                    spellcheck: "false",
+                    oninput: move |e| responder.with_mut(|r| r.edit(index, |rule| rule.pattern = e.value.clone())),
+                }
+                input {
+                    class: "form-control form-control-sm font-monospace",
+                    value: "{rule.response}",
+                    placeholder: "response, e.g. admin\\r\\n",
+                    spellcheck: "false",
+                    oninput: move |e| responder.with_mut(|r| r.edit(index, |rule| rule.response = e.value.clone())),
+                }
+                div {
+                    class: "d-flex gap-2",
+                    div {
+                        class: "input-group input-group-sm",
+                        input {
+                            class: "form-control",
+                            r#type: "number",
+                            min: "0",
+                            step: "100",
+                            value: "{delay}",
+                            oninput: move |e| {
+                                if let Ok(ms) = e.value.parse::<u64>() {
+                                    responder.with_mut(|r| r.edit(index, |rule| rule.delay = Duration::from_millis(ms)));
+                                }
+                            },
+                        }
+                        span { class: "input-group-text", "ms" }
+                    }
+                    select {
+                        class: "form-select form-select-sm",
+                        value: if rule.repeat { "repeat" } else { "once" },
+                        onchange: move |e| responder.with_mut(|r| r.edit(index, |rule| rule.repeat = e.value == "repeat")),
+                        option { value: "repeat", "Repeat" }
+                        option { value: "once", "One-shot" }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use dioxus::prelude::*;
+use log::*;
+use tokio::sync::mpsc::unbounded_channel;
+
+use serial_monitor::connection::Connection;
+
+use crate::script::{Script, ScriptEvent};
+
+const DEFAULT_SCRIPT: &str = r#"// send(text), send_line(text), expect(regex, timeout_s) -> captures,
+// sleep(seconds), set_dtr(bool), log(text), assert(cond, message)
+send_line("version");
+let m = expect("v(\\d+)\\.(\\d+)", 2);
+log(`firmware ${m[1]}.${m[2]}`);
+assert(m[1] == "1", "unexpected major version");
+"#;
+
+#[derive(Clone, PartialEq)]
+enum Status {
+    Idle,
+    Running,
+    Passed,
+    Failed(String),
+}
+
+#[inline_props]
+pub fn ScriptPanel(
+    cx: Scope,
+    connection: UseRef<Connection>,
+    user_buffer: UseRef<Vec<String>>,
+) -> Element {
+    let source = use_state(cx, || DEFAULT_SCRIPT.to_string());
+    let output = use_ref(cx, Vec::<String>::new);
+    let status = use_state(cx, || Status::Idle);
+    let script = use_ref(cx, || None::<Script>);
+
+    let run = move |_| {
+        output.with_mut(|o| o.clear());
+        status.set(Status::Running);
+        let (tx, mut rx) = unbounded_channel();
+        let received = connection.write().subscribe();
+        script.set(Some(Script::start(source.get().clone(), received, tx)));
+        cx.spawn({
+            to_owned![connection, user_buffer, output, status, script];
+            async move {
+                while let Some(event) = rx.recv().await {
+                    match event {
+                        ScriptEvent::Send(s) => {
+                            output.with_mut(|o| o.push(format!("> {}", s.trim_end())));
+                            user_buffer.with_mut(|b| b.push(s.clone()));
+                            if let Err(e) = connection.write().write(&s) {
+                                error!("{:?}", e);
+                                output.with_mut(|o| o.push(format!("send failed: {e}")));
+                            }
+                        }
+                        ScriptEvent::SetDtr(level) => {
+                            output.with_mut(|o| o.push(format!("> DTR {}", level as u8)));
+                            if let Err(e) = connection.write().set_dtr(level) {
+                                error!("{:?}", e);
+                                output.with_mut(|o| o.push(format!("set_dtr failed: {e}")));
+                            }
+                        }
+                        ScriptEvent::Log(s) => output.with_mut(|o| o.push(s)),
+                        ScriptEvent::Finished(res) => {
+                            status.set(match res {
+                                Ok(_) => Status::Passed,
+                                Err(e) => Status::Failed(e),
+                            });
+                            script.set(None);
+                            break;
+                        }
+                    }
+                }
+            }
+        })
+    };
+
+    let running = **status == Status::Running;
+    let (badge_class, badge_text) = match status.get() {
+        Status::Idle => ("badge text-bg-secondary", "Idle".to_string()),
+        Status::Running => ("badge text-bg-primary", "Running".to_string()),
+        Status::Passed => ("badge text-bg-success", "PASS".to_string()),
+        Status::Failed(e) => ("badge text-bg-danger text-wrap", format!("FAIL: {e}")),
+    };
+    let log = output.read().join("\n");
+
+    render! {
+        div {
+            class: "h-100 d-flex flex-column gap-2",
+            textarea {
+                class: "form-control font-monospace flex-grow-1",
+                font_size: "0.8rem",
+                spellcheck: "false",
+                resize: "none",
+                value: "{source}",
+                oninput: move |event| source.set(event.value.clone()),
+            }
+            div {
+                class: "d-flex gap-2 align-items-center",
+                if running {
+                    rsx! {
+                        button {
+                            class: "btn btn-outline-danger btn-sm",
+                            onclick: move |_| script.with(|s| s.iter().for_each(|s| s.stop())),
+                            "Stop"
+                        }
+                    }
+                } else {
+                    rsx! {
+                        button {
+                            class: "btn btn-primary btn-sm bg-gradient",
+                            onclick: run,
+                            "Run"
+                        }
+                    }
+                }
+                span { class: "{badge_class}", "{badge_text}" }
+            }
+            textarea {
+                class: "form-control font-monospace flex-grow-1",
+                font_size: "0.8rem",
+                readonly: true,
+                resize: "none",
+                "{log}"
+            }
+        }
+    }
+}
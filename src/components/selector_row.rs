@@ -1,32 +1,50 @@
 use dioxus::prelude::*;
 use log::*;
 
-use crate::{
-    api::{self, Connection},
+use serial_monitor::{
+    connection::{Connection, DEFAULT_BR},
+    highlight::Highlighter,
     ports::PortInfo,
+    recorder::{Recorder, RecordingSettings},
 };
 
+use crate::api;
+
 #[inline_props]
 pub fn SelectorRow(
     cx: Scope,
     available_ports: UseState<Vec<PortInfo>>,
     connection: UseRef<Connection>,
-    buffer: UseRef<Vec<String>>,
+    port: UseRef<Option<String>>,
+    session: Coroutine<Option<String>>,
+    highlighter: UseRef<Highlighter>,
+    recording: UseRef<RecordingSettings>,
+    recorder: UseRef<Option<Recorder>>,
 ) -> Element {
     render! {
         div {
             class: "row g-2",
             div {
-                class: "col-12 col-sm-8",
-                Selector { available_ports: available_ports.clone(), connection: connection.clone(), buffer: buffer.clone() }
+                class: "col-12 col-sm-7",
+                Selector {
+                    available_ports: available_ports.clone(),
+                    port: port.clone(),
+                    session: session.clone(),
+                }
             },
             div {
                 class: "col-10 col-sm-3",
                 BaudSelector { connection: connection.clone() }
             }
             div {
-                class: "col-2 col-sm-1 d-flex justify-content-center align-items-center",
+                class: "col-12 col-sm-2 d-flex justify-content-center align-items-center gap-3",
                 ConnectionIndicator { connection: connection.clone() }
+                RecordToggle {
+                    connection: connection.clone(),
+                    highlighter: highlighter.clone(),
+                    recording: recording.clone(),
+                    recorder: recorder.clone(),
+                }
             }
         }
     }
@@ -36,34 +54,20 @@ pub fn SelectorRow(
 fn Selector(
     cx: Scope,
     available_ports: UseState<Vec<PortInfo>>,
-    connection: UseRef<Connection>,
-    buffer: UseRef<Vec<String>>,
+    port: UseRef<Option<String>>,
+    session: Coroutine<Option<String>>,
 ) -> Element {
-    let prev_task: &UseState<Option<TaskId>> = use_state(cx, || None);
     let connect = move |e: Event<FormData>| {
-        if let Some(id) = **prev_task {
-            cx.remove_future(id);
-        }
-        connection.with_mut(|c| c.close());
-        if e.value != "none" {
-            let id = cx.push_future({
-                to_owned![connection, buffer, prev_task];
-                async move {
-                    api::connect(connection.clone(), &e.value).await;
-                    info!("Connected to {}", &e.value);
-                    api::read(connection.clone(), buffer.clone()).await;
-                    prev_task.set(None);
-                }
-            });
-            prev_task.set(Some(id));
-        }
+        session.send((e.value != "none").then(|| e.value.clone()));
     };
+    let selected = port.read().clone().unwrap_or_else(|| "none".to_string());
 
     render! {
         div {
             class: "form-floating",
             select {
                 class: "form-select",
+                value: "{selected}",
                 onchange: connect,
                 if available_ports.is_empty() {
                     rsx! { option { value: "none", "No ports detected" } }
@@ -84,7 +88,14 @@ fn Selector(
 
 #[inline_props]
 fn BaudSelector(cx: Scope, connection: UseRef<Connection>) -> Element {
-    let inp = use_state(cx, || format!("{}", api::DEFAULT_BR));
+    let inp = use_state(cx, || format!("{}", DEFAULT_BR));
+    // Follow changes made elsewhere, e.g. over the control socket.
+    let applied = use_state(cx, || DEFAULT_BR);
+    let baud_rate = connection.read().get_baud_rate();
+    if baud_rate != **applied {
+        applied.set(baud_rate);
+        inp.set(baud_rate.to_string());
+    }
     let set_br = |s: &str| {
         inp.set(s.to_string());
         match str::parse::<u32>(s) {
@@ -138,6 +149,60 @@ fn ConnectionIndicator(cx: Scope, connection: UseRef<Connection>) -> Element {
     }
 }
 
+#[inline_props]
+fn RecordToggle(
+    cx: Scope,
+    connection: UseRef<Connection>,
+    highlighter: UseRef<Highlighter>,
+    recording: UseRef<RecordingSettings>,
+    recorder: UseRef<Option<Recorder>>,
+) -> Element {
+    let path = recorder
+        .read()
+        .as_ref()
+        .and_then(|r| r.path())
+        .map(|p| p.display().to_string());
+    let toggle = move |_| {
+        if recorder.read().is_some() {
+            recorder.set(None);
+            return;
+        }
+        let settings = recording.read().clone();
+        let rules = highlighter.read().rules().to_vec();
+        match Recorder::start(settings, rules, connection.read().metadata()) {
+            Ok(r) => recorder.set(Some(r)),
+            Err(e) => {
+                error!("Failed to start recording: {:?}", e);
+                cx.spawn(async move {
+                    api::show_error_dialog("Recording failed", &format!("{e}")).await;
+                });
+            }
+        }
+    };
+
+    render! {
+        match path {
+            Some(path) => rsx! {
+                button {
+                    class: "btn btn-sm btn-danger text-nowrap",
+                    title: "Recording to {path}",
+                    onclick: toggle,
+                    span { class: "spinner-grow spinner-grow-sm me-1", style: "animation-duration: 1.5s;" }
+                    "REC"
+                }
+            },
+            None => rsx! {
+                button {
+                    class: "btn btn-sm btn-outline-danger text-nowrap",
+                    title: "Stream everything sent and received to disk",
+                    onclick: toggle,
+                    "● Record"
+                }
+            },
+        }
+    }
+}
+
 fn ConnectingSpinner(cx: Scope) -> Element {
     render! {
         div {
@@ -0,0 +1,251 @@
+use dioxus::prelude::*;
+
+use serial_monitor::{
+    ansi::AnsiMode, connection::DEFAULT_BR, highlight::Highlighter, ports::PortInfo,
+};
+
+use crate::{
+    components::consoles::Console,
+    sniffer::{Request, Route, Rule, Rules, Side, SnifferStatus},
+};
+
+#[inline_props]
+pub fn SnifferView(
+    cx: Scope,
+    available_ports: UseState<Vec<PortInfo>>,
+    sniffer: Coroutine<Request>,
+    rules: UseRef<Rules>,
+    buffer: UseRef<Vec<String>>,
+    status: UseRef<SnifferStatus>,
+    highlighter: UseRef<Highlighter>,
+    ansi_mode: AnsiMode,
+) -> Element {
+    let a = use_state(cx, || "none".to_string());
+    let b = use_state(cx, || "none".to_string());
+    let baud = use_state(cx, || DEFAULT_BR.to_string());
+    let status = status.read().clone();
+    let baud_rate = baud.parse::<u32>().ok();
+    let can_start = **a != "none" && **b != "none" && a != b && baud_rate.is_some();
+
+    let port_select = |side: Side, value: &UseState<String>| {
+        let value = value.clone();
+        rsx! {
+            div {
+                class: "input-group input-group-sm",
+                span { class: "input-group-text", side.name() }
+                select {
+                    class: "form-select",
+                    value: "{value}",
+                    disabled: status.running,
+                    onchange: move |e| value.set(e.value.clone()),
+                    option { value: "none", "Select port" }
+                    available_ports.iter().map(|inf| rsx! { option {
+                        value: inf.name(),
+                        format!("{}\t|\t{}", inf.name(), inf.product())
+                    }})
+                }
+            }
+        }
+    };
+
+    render! {
+        div {
+            class: "h-100 d-flex flex-column gap-2",
+            div {
+                class: "d-flex gap-2",
+                port_select(Side::A, a)
+                port_select(Side::B, b)
+                div {
+                    class: "input-group input-group-sm w-auto flex-nowrap",
+                    input {
+                        class: "form-control",
+                        r#type: "number",
+                        min: "0",
+                        step: "100",
+                        value: "{baud}",
+                        disabled: status.running,
+                        oninput: move |e| baud.set(e.value.clone()),
+                    }
+                    span { class: "input-group-text", "baud" }
+                }
+                if status.running {
+                    rsx! {
+                        button {
+                            class: "btn btn-sm btn-danger",
+                            onclick: move |_| sniffer.send(Request::Stop),
+                            "Stop"
+                        }
+                    }
+                } else {
+                    rsx! {
+                        button {
+                            class: "btn btn-sm btn-primary",
+                            disabled: !can_start,
+                            title: "Forward everything between A and B",
+                            onclick: move |_| {
+                                if let Some(baud_rate) = baud_rate {
+                                    sniffer.send(Request::Start { a: a.get().clone(), b: b.get().clone(), baud_rate });
+                                }
+                            },
+                            "Start"
+                        }
+                    }
+                }
+                button {
+                    class: "btn btn-sm btn-outline-secondary",
+                    onclick: move |_| buffer.set(Vec::new()),
+                    "Clear"
+                }
+            }
+            if let Some(e) = &status.error {
+                rsx! { div { class: "alert alert-danger py-1 px-2 mb-0 small", "{e}" } }
+            }
+            div {
+                class: "row g-2 flex-grow-1",
+                min_height: "1rem",
+                div {
+                    class: "col-12 col-md-4 h-100",
+                    RulesEditor { rules: rules.clone() }
+                }
+                div {
+                    class: "col-12 col-md h-100",
+                    Console { id: 2, buffer: buffer.clone(), highlighter: highlighter.clone(), ansi_mode: *ansi_mode }
+                }
+            }
+            Injector { sniffer: sniffer.clone(), running: status.running }
+        }
+    }
+}
+
+#[inline_props]
+fn Injector(cx: Scope, sniffer: Coroutine<Request>, running: bool) -> Element {
+    let side = use_state(cx, || Side::B);
+    let text = use_state(cx, String::new);
+    let inject = move || {
+        if !text.is_empty() {
+            sniffer.send(Request::Inject(**side, text.get().clone()));
+            text.set(String::new());
+        }
+    };
+
+    render! {
+        div {
+            class: "input-group input-group-sm",
+            select {
+                class: "form-select flex-grow-0 w-auto",
+                value: side.name(),
+                onchange: move |e| side.set(if e.value == "A" { Side::A } else { Side::B }),
+                option { value: "B", "Inject → B" }
+                option { value: "A", "Inject → A" }
+            }
+            input {
+                class: "form-control font-monospace",
+                value: "{text}",
+                placeholder: "e.g. AT\\r\\n",
+                spellcheck: "false",
+                disabled: !running,
+                oninput: move |e| text.set(e.value.clone()),
+                onkeypress: move |e| {
+                    if e.key() == dioxus::html::input_data::keyboard_types::Key::Enter {
+                        inject();
+                    }
+                },
+            }
+            button {
+                class: "btn btn-primary",
+                disabled: !running,
+                onclick: move |_| inject(),
+                "Inject"
+            }
+        }
+    }
+}
+
+#[inline_props]
+fn RulesEditor(cx: Scope, rules: UseRef<Rules>) -> Element {
+    let count = rules.read().count();
+
+    render! {
+        div {
+            class: "h-100 d-flex flex-column gap-2 overflow-auto",
+            div {
+                class: "form-text",
+                "Rules rewrite what they match in data passing through, in order. Data is matched a read at a time, so a match split across reads is missed. "
+                "Use $1 or ${{name}} for capture groups and \\r, \\n, \\xHH for control characters."
+            }
+            (0..count).map(|i| rsx! { RuleEditor { key: "{i}", index: i, rules: rules.clone() } })
+            button {
+                class: "btn btn-outline-primary btn-sm",
+                onclick: move |_| rules.with_mut(|r| r.push(Rule {
+                    name: format!("rule {}", count + 1),
+                    ..Default::default()
+                })),
+                "Add rule"
+            }
+        }
+    }
+}
+
+#[inline_props]
+fn RuleEditor(cx: Scope, index: usize, rules: UseRef<Rules>) -> Element {
+    let index = *index;
+    let (rule, valid) = rules.read().get(index)?;
+    let pattern_class = if valid {
+        "form-control form-control-sm font-monospace"
+    } else {
+        "form-control form-control-sm font-monospace is-invalid"
+    };
+
+    render! {
+        div {
+            class: "card",
+            div {
+                class: "card-body p-2 d-flex flex-column gap-1",
+                div {
+                    class: "d-flex gap-2 align-items-center",
+                    input {
+                        class: "form-check-input mt-0",
+                        r#type: "checkbox",
+                        checked: "{rule.enabled}",
+                        onchange: move |e| rules.with_mut(|r| r.edit(index, |rule| rule.enabled = e.value == "true")),
+                    }
+                    input {
+                        class: "form-control form-control-sm",
+                        value: "{rule.name}",
+                        placeholder: "name",
+                        oninput: move |e| rules.with_mut(|r| r.edit(index, |rule| rule.name = e.value.clone())),
+                    }
+                    select {
+                        class: "form-select form-select-sm w-auto",
+                        value: rule.route.name(),
+                        onchange: move |e| {
+                            if let Some(route) = Route::ALL.iter().find(|r| r.name() == e.value) {
+                                rules.with_mut(|r| r.edit(index, |rule| rule.route = *route));
+                            }
+                        },
+                        Route::ALL.iter().map(|r| rsx! { option { value: r.name(), r.name() } })
+                    }
+                    button {
+                        class: "btn btn-outline-danger btn-sm",
+                        onclick: move |_| rules.with_mut(|r| r.remove(index)),
+                        "✕"
+                    }
+                }
+                input {
+                    class: "{pattern_class}",
+                    value: "{rule.pattern}",
+                    placeholder: "regex, e.g. BAUD=(\\d+)",
+                    spellcheck: "false",
+                    oninput: move |e| rules.with_mut(|r| r.edit(index, |rule| rule.pattern = e.value.clone())),
+                }
+                input {
+                    class: "form-control form-control-sm font-monospace",
+                    value: "{rule.replacement}",
+                    placeholder: "replacement, e.g. BAUD=9600",
+                    spellcheck: "false",
+                    oninput: move |e| rules.with_mut(|r| r.edit(index, |rule| rule.replacement = e.value.clone())),
+                }
+            }
+        }
+    }
+}
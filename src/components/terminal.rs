@@ -0,0 +1,149 @@
+use dioxus::prelude::*;
+use log::*;
+
+use serial_monitor::connection::Connection;
+
+use crate::terminal::{self, BackspaceKey, SCROLLBACK, SIZES};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SessionMode {
+    Console,
+    Terminal,
+    File,
+    Sniffer,
+}
+
+#[inline_props]
+pub fn SessionModeSelector(cx: Scope, mode: UseState<SessionMode>) -> Element {
+    let class = |m: SessionMode| {
+        if **mode == m {
+            "btn btn-secondary"
+        } else {
+            "btn btn-outline-secondary"
+        }
+    };
+    render! {
+        div {
+            class: "btn-group btn-group-sm",
+            role: "group",
+            button {
+                class: class(SessionMode::Console),
+                onclick: move |_| mode.set(SessionMode::Console),
+                "Console"
+            }
+            button {
+                class: class(SessionMode::Terminal),
+                onclick: move |_| mode.set(SessionMode::Terminal),
+                "Terminal"
+            }
+            button {
+                class: class(SessionMode::File),
+                title: "Browse a large capture file",
+                onclick: move |_| mode.set(SessionMode::File),
+                "File"
+            }
+            button {
+                class: class(SessionMode::Sniffer),
+                title: "Forward and watch the traffic between two ports",
+                onclick: move |_| mode.set(SessionMode::Sniffer),
+                "Sniffer"
+            }
+        }
+    }
+}
+
+#[inline_props]
+pub fn Terminal(cx: Scope, connection: UseRef<Connection>) -> Element {
+    let (default_rows, default_cols) = SIZES[0];
+    let parser = use_ref(cx, || {
+        vt100::Parser::new(default_rows, default_cols, SCROLLBACK)
+    });
+
+    use_future(cx, (), |_| {
+        to_owned![connection, parser];
+        async move {
+            let mut rx = connection.write_silent().subscribe_raw();
+            while let Some(chunk) = rx.recv().await {
+                parser.with_mut(|p| p.process(&chunk.data));
+            }
+        }
+    });
+
+    let send = move |s: String| {
+        parser.with_mut(|p| p.set_scrollback(0));
+        if let Err(e) = connection.write().write(&s) {
+            error!("{:?}", e);
+        }
+    };
+
+    let parser_ref = parser.read();
+    let screen = parser_ref.screen();
+    let application_cursor = screen.application_cursor();
+    let (rows, cols) = screen.size();
+    let size = format!("{cols}x{rows}");
+    let title = screen.title().to_string();
+    let alternate = screen.alternate_screen();
+    let scrollback = screen.scrollback();
+    let content = terminal::rows(screen);
+    drop(parser_ref);
+
+    render! {
+        div {
+            class: "h-100 d-flex flex-column gap-1",
+            div {
+                class: "d-flex gap-2 align-items-center",
+                select {
+                    class: "form-select form-select-sm w-auto",
+                    value: "{size}",
+                    onchange: move |e| {
+                        if let Some((r, c)) = SIZES.iter().find(|(r, c)| format!("{c}x{r}") == e.value) {
+                            parser.with_mut(|p| p.set_size(*r, *c));
+                        }
+                    },
+                    SIZES.iter().map(|(r, c)| rsx! { option { value: "{c}x{r}", "{c}×{r}" } })
+                }
+                if alternate {
+                    rsx! { span { class: "badge text-bg-info", "alternate screen" } }
+                }
+                if scrollback > 0 {
+                    rsx! { span { class: "badge text-bg-warning", "scrollback -{scrollback}" } }
+                }
+                span { class: "text-secondary text-truncate small", "{title}" }
+            }
+            div {
+                class: "form-control flex-grow-1 overflow-auto font-monospace p-2",
+                tabindex: "0",
+                font_size: "0.875rem",
+                line_height: "1.2",
+                white_space: "pre",
+                prevent_default: "onkeydown",
+                onkeydown: move |e| {
+                    let key = terminal::encode_key(&e.key(), e.modifiers(), application_cursor, BackspaceKey::Del);
+                    if let Some(s) = key {
+                        send(s);
+                    }
+                },
+                onwheel: move |e| {
+                    let up = e.delta().strip_units().y < 0.0;
+                    parser.with_mut(|p| {
+                        let current = p.screen().scrollback();
+                        p.set_scrollback(if up { current + 3 } else { current.saturating_sub(3) });
+                    });
+                },
+                content.into_iter().enumerate().map(|(i, row)| rsx! {
+                    div {
+                        key: "{i}",
+                        row.into_iter().map(|run| {
+                            let css = run.style.css();
+                            if run.cursor {
+                                rsx! { span { style: "{css}background-color: var(--bs-body-color); color: var(--bs-body-bg);", "{run.text}" } }
+                            } else {
+                                rsx! { span { style: "{css}", "{run.text}" } }
+                            }
+                        })
+                    }
+                })
+            }
+        }
+    }
+}
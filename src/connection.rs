@@ -0,0 +1,278 @@
+//! A serial port connection that keeps a log of the session and fans
+//! received data out to subscribers.
+
+use std::io::{Error, ErrorKind::NotConnected};
+
+use log::*;
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    time::Duration,
+};
+
+use crate::{
+    export::Metadata,
+    handle::{self, Chunk, Handle, LineSettings},
+    ports::{self, UsbIds},
+    record::{Direction, Record},
+};
+
+pub const DEFAULT_BR: u32 = 9600;
+/// How long [`Connection::send_break`] holds the line, well above the
+/// frame time at any common baud rate.
+pub const BREAK_TIME: Duration = Duration::from_millis(250);
+/// How many records the log keeps. The oldest are dropped beyond that, so
+/// long sessions should be recorded to disk instead.
+pub const LOG_LIMIT: usize = 200_000;
+
+/// A port that may or may not be open, with its settings and everything
+/// sent and received through it.
+///
+/// Reading is polled: call [`Connection::read`] regularly (the apps use
+/// 20 ms) from within a Tokio runtime. Each call hands received data to the
+/// caller, to every [`Connection::subscribe`]r and to the record log.
+#[derive(Debug)]
+pub struct Connection {
+    handle: Option<Handle>,
+    name: Option<String>,
+    baud_rate: u32,
+    line_settings: LineSettings,
+    usb: Option<UsbIds>,
+    subscribers: Vec<UnboundedSender<String>>,
+    record_subscribers: Vec<UnboundedSender<Record>>,
+    raw_subscribers: Vec<UnboundedSender<Chunk>>,
+    log: Vec<Record>,
+    /// Records dropped from the front of `log` so far.
+    log_offset: usize,
+    logging: bool,
+}
+
+impl Connection {
+    /// A closed connection that will open ports at `baud_rate`.
+    pub fn new(baud_rate: u32) -> Self {
+        Self {
+            handle: None,
+            name: None,
+            baud_rate,
+            line_settings: LineSettings::default(),
+            usb: None,
+            subscribers: Vec::new(),
+            record_subscribers: Vec::new(),
+            raw_subscribers: Vec::new(),
+            log: Vec::new(),
+            log_offset: 0,
+            logging: true,
+        }
+    }
+
+    /// Opens `port`, replacing any port already open. The log is cleared if
+    /// it is of another port.
+    #[must_use]
+    pub fn open(&mut self, port: &str) -> handle::Result<()> {
+        self.handle = Some(Handle::open(port, self.baud_rate, self.line_settings)?);
+        if self.log.last().is_some_and(|r| r.port != port) {
+            self.clear_log();
+        }
+        self.attach_raw_subscribers();
+        self.name = Some(port.to_string());
+        self.usb = ports::find(port).and_then(|p| p.usb().cloned());
+        Ok(())
+    }
+
+    pub fn close(&mut self) {
+        self.handle = None;
+        self.name = None;
+        self.usb = None;
+    }
+
+    /// Describes the current port and settings for export headers.
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            port: self.get_name().to_string(),
+            vid: self.usb.as_ref().map(|u| format!("{:04x}", u.vid)),
+            pid: self.usb.as_ref().map(|u| format!("{:04x}", u.pid)),
+            serial_number: self.usb.as_ref().and_then(|u| u.serial_number.clone()),
+            baud_rate: self.baud_rate,
+            line_settings: self.line_settings.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn get_baud_rate(&self) -> u32 {
+        self.baud_rate
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.name.as_ref().map(|s| s.as_str()).unwrap_or("none")
+    }
+
+    /// Applies `rate`, reopening the port if one is open.
+    #[must_use]
+    pub fn set_baud_rate(&mut self, rate: u32) -> handle::Result<()> {
+        if self.handle.is_none() {
+            self.baud_rate = rate;
+            return Ok(());
+        }
+        self.baud_rate = rate;
+        debug!("{}", self.baud_rate);
+        let name = self.name.as_ref().unwrap();
+        self.handle
+            .as_mut()
+            .unwrap()
+            .reconnect(name, rate, self.line_settings)?;
+        self.attach_raw_subscribers();
+        Ok(())
+    }
+
+    /// Applies `settings`, reopening the port if one is open.
+    pub fn set_line_settings(&mut self, settings: LineSettings) -> handle::Result<()> {
+        self.line_settings = settings;
+        match (self.handle.as_mut(), self.name.as_ref()) {
+            (Some(handle), Some(name)) => handle.reconnect(name, self.baud_rate, settings)?,
+            _ => return Ok(()),
+        }
+        self.attach_raw_subscribers();
+        Ok(())
+    }
+
+    /// Whether a port is open and its IO task still running.
+    pub fn is_connected(&self) -> bool {
+        self.handle
+            .as_ref()
+            .map(|h| h.is_connected())
+            .unwrap_or(false)
+    }
+
+    /// Whether a port was opened and not closed, even if it has since been
+    /// lost.
+    pub fn has_handle(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    pub fn write(&mut self, data: &str) -> handle::Result<()> {
+        self.write_bytes(data.as_bytes())
+    }
+
+    /// Queues `data` for sending and logs it.
+    pub fn write_bytes(&mut self, data: &[u8]) -> handle::Result<()> {
+        self.handle
+            .as_ref()
+            .ok_or(Error::new(NotConnected, "Not connected"))
+            .and_then(|h| h.write_bytes(data))?;
+        self.push_record(Record::new(Direction::Tx, self.get_name(), data.to_vec()));
+        Ok(())
+    }
+
+    pub fn set_dtr(&mut self, level: bool) -> handle::Result<()> {
+        self.handle
+            .as_ref()
+            .ok_or(Error::new(NotConnected, "Not connected"))
+            .and_then(|h| h.set_dtr(level))
+    }
+
+    pub fn send_break(&mut self) -> handle::Result<()> {
+        self.handle
+            .as_ref()
+            .ok_or(Error::new(NotConnected, "Not connected"))
+            .and_then(|h| h.send_break(BREAK_TIME))
+    }
+
+    /// Returns what arrived since the last call, lossily decoded, or an
+    /// empty string. Never waits.
+    pub fn read(&mut self) -> handle::Result<String> {
+        let chunk = self
+            .handle
+            .as_mut()
+            .ok_or(Error::new(NotConnected, "Not connected"))
+            .and_then(|h| h.read())?;
+        let Some(chunk) = chunk else {
+            return Ok(String::new());
+        };
+        let data = String::from_utf8_lossy(&chunk.data).into_owned();
+        self.subscribers.retain(|s| s.send(data.clone()).is_ok());
+        self.push_record(Record {
+            time: chunk.time,
+            ..Record::new(Direction::Rx, self.get_name(), chunk.data)
+        });
+        Ok(data)
+    }
+
+    fn push_record(&mut self, record: Record) {
+        self.record_subscribers
+            .retain(|s| s.send(record.clone()).is_ok());
+        if self.logging {
+            if self.log.len() >= LOG_LIMIT {
+                let dropped = LOG_LIMIT / 10;
+                self.log.drain(..dropped);
+                self.log_offset += dropped;
+            }
+            self.log.push(record);
+        }
+    }
+
+    /// Forgets every logged record, e.g. when the console is cleared.
+    pub fn clear_log(&mut self) {
+        self.log_offset += self.log.len();
+        self.log = Vec::new();
+    }
+
+    /// Whether records are kept for [`Connection::records`]. On by default;
+    /// long-running callers that only subscribe can turn it off.
+    pub fn set_logging(&mut self, enabled: bool) {
+        self.logging = enabled;
+        if !enabled {
+            self.clear_log();
+        }
+    }
+
+    /// Everything sent and received through this connection since the log
+    /// was last cleared, in order, up to the last `LOG_LIMIT` records.
+    pub fn records(&self) -> &[Record] {
+        &self.log
+    }
+
+    /// How many records were logged before the first one in `records`, so
+    /// that positions in the log stay valid as old records are dropped.
+    pub fn log_offset(&self) -> usize {
+        self.log_offset
+    }
+
+    /// Returns a channel receiving a copy of everything read from the port
+    /// from now on. The subscription ends when the receiver is dropped.
+    pub fn subscribe(&mut self) -> UnboundedReceiver<String> {
+        let (tx, rx) = unbounded_channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Returns a channel receiving what the port sends as soon as it is
+    /// read, without framing and without waiting for `read`. Unlike
+    /// `subscribe` it carries the bytes as they are, for terminals.
+    pub fn subscribe_raw(&mut self) -> UnboundedReceiver<Chunk> {
+        let (tx, rx) = unbounded_channel();
+        if let Some(handle) = &self.handle {
+            let _ = handle.subscribe_raw(tx.clone());
+        }
+        self.raw_subscribers.push(tx);
+        rx
+    }
+
+    /// Raw subscriptions live in the port's IO task, so a new one needs them
+    /// all again.
+    fn attach_raw_subscribers(&mut self) {
+        self.raw_subscribers.retain(|s| !s.is_closed());
+        if let Some(handle) = &self.handle {
+            for s in &self.raw_subscribers {
+                let _ = handle.subscribe_raw(s.clone());
+            }
+        }
+    }
+
+    /// Like `subscribe`, but for every record sent or received, with its
+    /// timestamp and raw bytes.
+    pub fn subscribe_records(&mut self) -> UnboundedReceiver<Record> {
+        let (tx, rx) = unbounded_channel();
+        self.record_subscribers.push(tx);
+        rx
+    }
+}
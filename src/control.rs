@@ -0,0 +1,345 @@
+use std::{fmt, io, net::SocketAddr, str::FromStr};
+#[cfg(unix)]
+use std::{os::unix::fs::FileTypeExt, path::PathBuf};
+
+use log::*;
+use serde_json::{json, Value};
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    sync::{
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+};
+
+use serial_monitor::record::{Direction, Record};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+/// Where the control server listens. TCP is only accepted on loopback, as
+/// anyone who can connect can drive the port.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Endpoint {
+    #[cfg(unix)]
+    Unix(PathBuf),
+    Tcp(SocketAddr),
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<SocketAddr>() {
+            Ok(addr) if addr.ip().is_loopback() => Ok(Endpoint::Tcp(addr)),
+            Ok(addr) => Err(format!("{} is not a loopback address", addr.ip())),
+            #[cfg(unix)]
+            Err(_) => Ok(Endpoint::Unix(PathBuf::from(s))),
+            #[cfg(not(unix))]
+            Err(_) => Err("expected an address like 127.0.0.1:7878".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(unix)]
+            Endpoint::Unix(path) => write!(f, "{}", path.display()),
+            Endpoint::Tcp(addr) => write!(f, "{addr}"),
+        }
+    }
+}
+
+/// A request the app has to carry out on its connection.
+#[derive(Debug)]
+pub enum Method {
+    ListPorts,
+    Status,
+    /// Switch to a port, or disconnect with `None`. Answered once done, or
+    /// with an error after a few seconds while the app keeps retrying.
+    Connect(Option<String>),
+    Send(String),
+    SetBaud(u32),
+    StartRecording,
+    StopRecording,
+    Subscribe,
+}
+
+pub enum Response {
+    Value(Value),
+    /// Everything sent and received from now on, for `subscribe`.
+    Records(UnboundedReceiver<Record>),
+}
+
+pub struct Call {
+    pub method: Method,
+    pub reply: oneshot::Sender<Result<Response, String>>,
+}
+
+/// Accepts clients on `endpoint` and passes their requests on to `calls`.
+/// Each client speaks JSON-RPC 2.0, one message per line.
+pub async fn serve(endpoint: Endpoint, calls: UnboundedSender<Call>) -> io::Result<()> {
+    info!("Control server listening on {endpoint}");
+    match endpoint {
+        #[cfg(unix)]
+        Endpoint::Unix(path) => {
+            // A socket left behind by an earlier run would make bind fail,
+            // but anything else at that path is not ours to delete.
+            match std::fs::symlink_metadata(&path) {
+                Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(&path)?,
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} exists and is not a socket", path.display()),
+                    ))
+                }
+                Err(_) => (),
+            }
+            let listener = UnixListener::bind(&path)?;
+            loop {
+                let (stream, _) = listener.accept().await?;
+                tokio::spawn(run_client(stream, calls.clone()));
+            }
+        }
+        Endpoint::Tcp(addr) => {
+            let listener = TcpListener::bind(addr).await?;
+            loop {
+                let (stream, peer) = listener.accept().await?;
+                debug!("Control client connected from {peer}");
+                tokio::spawn(run_client(stream, calls.clone()));
+            }
+        }
+    }
+}
+
+async fn run_client(stream: impl AsyncRead + AsyncWrite, calls: UnboundedSender<Call>) {
+    if let Err(e) = client(stream, calls).await {
+        debug!("Control client left: {:?}", e);
+    }
+}
+
+async fn client(
+    stream: impl AsyncRead + AsyncWrite,
+    calls: UnboundedSender<Call>,
+) -> io::Result<()> {
+    let (read, mut write) = tokio::io::split(stream);
+    let mut lines = BufReader::new(read).lines();
+    let mut records: Option<UnboundedReceiver<Record>> = None;
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    return Ok(());
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let (id, res) = match parse(&line) {
+                    Ok((id, Request::Unsubscribe)) => {
+                        records = None;
+                        (id, Ok(json!(true)))
+                    }
+                    Ok((id, Request::Call(method))) => {
+                        let res = call(&calls, method).await.map(|res| match res {
+                            Response::Value(value) => value,
+                            Response::Records(rx) => {
+                                records = Some(rx);
+                                json!(true)
+                            }
+                        });
+                        (id, res.map_err(|e| (SERVER_ERROR, e)))
+                    }
+                    Err((id, e)) => (Some(id), Err(e)),
+                };
+                // Requests without an id are notifications and get no answer.
+                let Some(id) = id else {
+                    continue;
+                };
+                let msg = match res {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, message)) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": message },
+                    }),
+                };
+                send(&mut write, &msg).await?;
+            }
+            record = next_record(&mut records) => match record {
+                Some(record) if record.direction == Direction::Rx => {
+                    let msg = json!({
+                        "jsonrpc": "2.0",
+                        "method": "rx",
+                        "params": {
+                            "timestamp": record.unix_time(),
+                            "port": record.port,
+                            "text": record.text(),
+                            "hex": record.hex(),
+                        },
+                    });
+                    send(&mut write, &msg).await?;
+                }
+                Some(_) => (),
+                None => records = None,
+            },
+        }
+    }
+}
+
+async fn next_record(records: &mut Option<UnboundedReceiver<Record>>) -> Option<Record> {
+    match records {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn send(write: &mut (impl AsyncWrite + Unpin), msg: &Value) -> io::Result<()> {
+    let mut line = msg.to_string();
+    line.push('\n');
+    write.write_all(line.as_bytes()).await
+}
+
+async fn call(calls: &UnboundedSender<Call>, method: Method) -> Result<Response, String> {
+    let (reply, rx) = oneshot::channel();
+    calls
+        .send(Call { method, reply })
+        .map_err(|_| "The app is shutting down".to_string())?;
+    rx.await
+        .map_err(|_| "The app dropped the request".to_string())?
+}
+
+enum Request {
+    Call(Method),
+    /// Handled by the client itself, as it owns the subscription.
+    Unsubscribe,
+}
+
+type ParseError = (Value, (i64, String));
+
+fn parse(line: &str) -> Result<(Option<Value>, Request), ParseError> {
+    let msg: Value =
+        serde_json::from_str(line).map_err(|e| (Value::Null, (PARSE_ERROR, e.to_string())))?;
+    let id = msg.get("id").cloned();
+    let invalid = |e: &str| {
+        (
+            id.clone().unwrap_or(Value::Null),
+            (INVALID_REQUEST, e.to_string()),
+        )
+    };
+    if msg.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+        return Err(invalid("Expected \"jsonrpc\": \"2.0\""));
+    }
+    let Some(method) = msg.get("method").and_then(Value::as_str) else {
+        return Err(invalid("Missing method"));
+    };
+    let params = msg.get("params").cloned().unwrap_or(Value::Null);
+    let param = |name: &str| params.get(name).unwrap_or(&Value::Null);
+    let bad_params = |e: &str| {
+        (
+            id.clone().unwrap_or(Value::Null),
+            (INVALID_PARAMS, e.to_string()),
+        )
+    };
+
+    let request = match method {
+        "list_ports" => Request::Call(Method::ListPorts),
+        "status" => Request::Call(Method::Status),
+        "connect" => match param("port") {
+            Value::String(port) => Request::Call(Method::Connect(Some(port.clone()))),
+            _ => return Err(bad_params("Expected {\"port\": string}")),
+        },
+        "disconnect" => Request::Call(Method::Connect(None)),
+        "send" => match param("data") {
+            Value::String(data) => Request::Call(Method::Send(data.clone())),
+            _ => return Err(bad_params("Expected {\"data\": string}")),
+        },
+        "set_baud" => match param("baud").as_u64().and_then(|b| u32::try_from(b).ok()) {
+            Some(baud) => Request::Call(Method::SetBaud(baud)),
+            None => return Err(bad_params("Expected {\"baud\": number}")),
+        },
+        "start_recording" => Request::Call(Method::StartRecording),
+        "stop_recording" => Request::Call(Method::StopRecording),
+        "subscribe" => Request::Call(Method::Subscribe),
+        "unsubscribe" => Request::Unsubscribe,
+        _ => {
+            return Err((
+                id.unwrap_or(Value::Null),
+                (METHOD_NOT_FOUND, format!("Unknown method {method}")),
+            ))
+        }
+    };
+    Ok((id, request))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_code(line: &str) -> (Value, i64) {
+        match parse(line) {
+            Err((id, (code, _))) => (id, code),
+            Ok(_) => panic!("{line} parsed"),
+        }
+    }
+
+    #[test]
+    fn parses_calls() {
+        let (id, request) =
+            parse(r#"{"jsonrpc": "2.0", "id": 7, "method": "set_baud", "params": {"baud": 9600}}"#)
+                .unwrap();
+        assert_eq!(id, Some(json!(7)));
+        assert!(matches!(request, Request::Call(Method::SetBaud(9600))));
+
+        let (id, request) = parse(r#"{"jsonrpc": "2.0", "method": "disconnect"}"#).unwrap();
+        assert_eq!(id, None);
+        assert!(matches!(request, Request::Call(Method::Connect(None))));
+
+        let (_, request) =
+            parse(r#"{"jsonrpc": "2.0", "id": 1, "method": "unsubscribe"}"#).unwrap();
+        assert!(matches!(request, Request::Unsubscribe));
+    }
+
+    #[test]
+    fn reports_errors_with_the_id() {
+        assert_eq!(error_code("{"), (Value::Null, PARSE_ERROR));
+        assert_eq!(
+            error_code(r#"{"id": 1, "method": "status"}"#),
+            (json!(1), INVALID_REQUEST)
+        );
+        assert_eq!(
+            error_code(r#"{"jsonrpc": "2.0", "id": "a", "method": "reboot"}"#),
+            (json!("a"), METHOD_NOT_FOUND)
+        );
+        assert_eq!(
+            error_code(
+                r#"{"jsonrpc": "2.0", "id": 2, "method": "set_baud", "params": {"baud": -1}}"#
+            ),
+            (json!(2), INVALID_PARAMS)
+        );
+        assert_eq!(
+            error_code(r#"{"jsonrpc": "2.0", "id": 3, "method": "connect"}"#),
+            (json!(3), INVALID_PARAMS)
+        );
+    }
+
+    #[test]
+    fn endpoints() {
+        assert_eq!(
+            "127.0.0.1:7878".parse(),
+            Ok(Endpoint::Tcp("127.0.0.1:7878".parse().unwrap()))
+        );
+        assert!("0.0.0.0:7878".parse::<Endpoint>().is_err());
+        #[cfg(unix)]
+        assert_eq!(
+            "/tmp/serial.sock".parse(),
+            Ok(Endpoint::Unix(PathBuf::from("/tmp/serial.sock")))
+        );
+    }
+}
@@ -0,0 +1,334 @@
+use std::{collections::VecDeque, fmt::Write, sync::OnceLock};
+
+use regex::Regex;
+
+use serial_monitor::plot::{self, LineAssembler};
+
+const HISTORY_LEN: usize = 100;
+
+fn pair_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"([A-Za-z_][\w.]*)=([-+]?\d*\.?\d+(?:[eE][-+]?\d+)?)").unwrap())
+}
+
+/// Extracts every `key=value` pair with a numeric value from `line`.
+pub fn parse_pairs(line: &str) -> Vec<(String, f64)> {
+    pair_regex()
+        .captures_iter(line)
+        .filter_map(|c| {
+            let value = c[2].parse::<f64>().ok().filter(|v| v.is_finite())?;
+            Some((c[1].to_string(), value))
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Thresholds {
+    pub low: Option<f64>,
+    pub high: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Crossing {
+    Above(f64),
+    Below(f64),
+    Cleared,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlarmEvent {
+    pub metric: String,
+    pub value: f64,
+    pub crossing: Crossing,
+}
+
+impl AlarmEvent {
+    pub fn describe(&self) -> String {
+        match self.crossing {
+            Crossing::Above(limit) => format!("{}={} above {limit}", self.metric, self.value),
+            Crossing::Below(limit) => format!("{}={} below {limit}", self.metric, self.value),
+            Crossing::Cleared => format!("{}={} back in range", self.metric, self.value),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Metric {
+    pub name: String,
+    pub last: f64,
+    pub min: f64,
+    pub max: f64,
+    sum: f64,
+    count: u64,
+    /// Recent samples as (Unix time, value).
+    pub history: VecDeque<(f64, f64)>,
+    pub thresholds: Thresholds,
+    /// The limit the latest value is past, if any.
+    alarm: Option<Crossing>,
+}
+
+impl Metric {
+    fn new(name: &str, value: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            last: value,
+            min: value,
+            max: value,
+            sum: 0.0,
+            count: 0,
+            history: VecDeque::new(),
+            thresholds: Thresholds::default(),
+            alarm: None,
+        }
+    }
+
+    pub fn in_alarm(&self) -> bool {
+        self.alarm.is_some()
+    }
+
+    pub fn average(&self) -> f64 {
+        if self.count == 0 {
+            self.last
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    fn push(&mut self, time: f64, value: f64) -> Option<AlarmEvent> {
+        self.last = value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.count += 1;
+        self.history.push_back((time, value));
+        if self.history.len() > HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.check()
+    }
+
+    /// Re-evaluates the alarm state against the latest value and reports a
+    /// change, if any.
+    fn check(&mut self) -> Option<AlarmEvent> {
+        let crossing = match self.thresholds {
+            Thresholds { high: Some(h), .. } if self.last > h => Some(Crossing::Above(h)),
+            Thresholds { low: Some(l), .. } if self.last < l => Some(Crossing::Below(l)),
+            _ => None,
+        };
+        if crossing == self.alarm {
+            return None;
+        }
+        self.alarm = crossing;
+        Some(AlarmEvent {
+            metric: self.name.clone(),
+            value: self.last,
+            crossing: crossing.unwrap_or(Crossing::Cleared),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    metrics: Vec<Metric>,
+    lines: LineAssembler,
+}
+
+impl Dashboard {
+    pub fn metrics(&self) -> &[Metric] {
+        &self.metrics
+    }
+
+    /// Feeds a received frame and returns the alarms it raised or cleared.
+    pub fn push(&mut self, data: &str) -> Vec<AlarmEvent> {
+        self.push_at(plot::unix_time(), data)
+    }
+
+    /// Like `push`, for data received at `time` (Unix seconds).
+    pub fn push_at(&mut self, time: f64, data: &str) -> Vec<AlarmEvent> {
+        let mut events = Vec::new();
+        for line in self.lines.push(data) {
+            for (name, value) in parse_pairs(&line) {
+                let metric = match self.metrics.iter_mut().position(|m| m.name == name) {
+                    Some(i) => &mut self.metrics[i],
+                    None => {
+                        self.metrics.push(Metric::new(&name, value));
+                        self.metrics.last_mut().unwrap()
+                    }
+                };
+                events.extend(metric.push(time, value));
+            }
+        }
+        events
+    }
+
+    pub fn set_thresholds(&mut self, index: usize, thresholds: Thresholds) -> Option<AlarmEvent> {
+        let metric = self.metrics.get_mut(index)?;
+        metric.thresholds = thresholds;
+        metric.check()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+fn sparkline_points(history: &VecDeque<(f64, f64)>, x: f64, y: f64, w: f64, h: f64) -> String {
+    let lo = history
+        .iter()
+        .map(|(_, v)| *v)
+        .fold(f64::INFINITY, f64::min);
+    let hi = history
+        .iter()
+        .map(|(_, v)| *v)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let span = if hi > lo { hi - lo } else { 1.0 };
+    let step = w / (HISTORY_LEN - 1) as f64;
+    let offset = (HISTORY_LEN - history.len()) as f64 * step;
+    history
+        .iter()
+        .enumerate()
+        .map(|(i, (_, v))| {
+            format!(
+                "{:.1},{:.1}",
+                x + offset + i as f64 * step,
+                y + h - 2.0 - (v - lo) / span * (h - 4.0)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn sparkline_svg(history: &VecDeque<(f64, f64)>, color: &str) -> String {
+    const W: f64 = 120.0;
+    const H: f64 = 28.0;
+    let points = sparkline_points(history, 0.0, 0.0, W, H);
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {W} {H}" width="100%" height="{H}" preserveAspectRatio="none"><polyline points="{points}" fill="none" stroke="{color}" stroke-width="1.5" vector-effect="non-scaling-stroke"/></svg>"#
+    );
+    svg
+}
+
+/// Renders every metric as a row with its statistics and sparkline.
+pub fn export_svg(dashboard: &Dashboard) -> String {
+    const W: f64 = 600.0;
+    const ROW: f64 = 40.0;
+    let height = ROW * dashboard.metrics.len().max(1) as f64;
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {W} {height}" width="{W}" height="{height}" font-family="sans-serif" font-size="11"><rect x="0" y="0" width="{W}" height="{height}" fill="#212529"/>"##
+    );
+    for (i, m) in dashboard.metrics.iter().enumerate() {
+        let y = ROW * i as f64;
+        let border = if m.in_alarm() { "#dc3545" } else { "#495057" };
+        let _ = write!(
+            svg,
+            r##"<rect x="4" y="{:.1}" width="{}" height="{}" rx="4" fill="none" stroke="{border}"/><text x="12" y="{:.1}" fill="#adb5bd">{}</text><text x="12" y="{:.1}" fill="#dee2e6" font-family="monospace">{} (min {} avg {} max {})</text><polyline points="{}" fill="none" stroke="{}" stroke-width="1.5"/>"##,
+            y + 2.0,
+            W - 8.0,
+            ROW - 4.0,
+            y + 16.0,
+            plot::escape_xml(&m.name),
+            y + 32.0,
+            m.last,
+            m.min,
+            m.average(),
+            m.max,
+            sparkline_points(&m.history, W / 2.0, y + 4.0, W / 2.0 - 12.0, ROW - 8.0),
+            plot::color(i)
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Writes the retained history of every metric as CSV, one sample per row.
+pub fn export_csv(dashboard: &Dashboard) -> csv::Result<String> {
+    let mut samples = dashboard
+        .metrics
+        .iter()
+        .flat_map(|m| m.history.iter().map(move |&(t, v)| (t, &m.name, v)))
+        .collect::<Vec<_>>();
+    samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record(["unix_time", "metric", "value"])?;
+    for (t, name, v) in samples {
+        wtr.write_record([format!("{t:.3}"), name.clone(), v.to_string()])?;
+    }
+    let data = wtr.into_inner().map_err(|e| e.into_error())?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs() {
+        assert_eq!(
+            parse_pairs("temp=21.5 rh=-3 x=1e3 v=.5 name=abc 9=1"),
+            [
+                ("temp".to_string(), 21.5),
+                ("rh".to_string(), -3.0),
+                ("x".to_string(), 1000.0),
+                ("v".to_string(), 0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn non_finite_values_are_ignored() {
+        assert_eq!(parse_pairs("a=1e999 b=2"), [("b".to_string(), 2.0)]);
+    }
+
+    fn crossings(dashboard: &mut Dashboard, data: &str) -> Vec<Crossing> {
+        dashboard
+            .push_at(0.0, data)
+            .into_iter()
+            .map(|e| e.crossing)
+            .collect()
+    }
+
+    #[test]
+    fn alarms_change_state_once() {
+        let mut dashboard = Dashboard::default();
+        dashboard.push_at(0.0, "t=5\n");
+        let thresholds = Thresholds {
+            low: Some(0.0),
+            high: Some(10.0),
+        };
+        assert_eq!(dashboard.set_thresholds(0, thresholds), None);
+        assert_eq!(
+            crossings(&mut dashboard, "t=11\nt=12\n"),
+            [Crossing::Above(10.0)]
+        );
+        assert!(dashboard.metrics()[0].in_alarm());
+        // At the limit is still in range.
+        assert_eq!(
+            crossings(&mut dashboard, "t=10\nt=9\n"),
+            [Crossing::Cleared]
+        );
+        assert_eq!(
+            crossings(&mut dashboard, "t=-1\nt=-2\n"),
+            [Crossing::Below(0.0)]
+        );
+        assert_eq!(crossings(&mut dashboard, "t=20\n"), [Crossing::Above(10.0)]);
+        let event = dashboard.set_thresholds(0, Thresholds::default()).unwrap();
+        assert_eq!(event.describe(), "t=20 back in range");
+        assert!(!dashboard.metrics()[0].in_alarm());
+    }
+
+    #[test]
+    fn csv_is_in_time_order() {
+        let mut dashboard = Dashboard::default();
+        dashboard.push_at(2.0, "a=1 b=2\n");
+        dashboard.push_at(1.0, "b=3\n");
+        assert_eq!(
+            export_csv(&dashboard).unwrap(),
+            "unix_time,metric,value\n1.000,b,3\n2.000,a,1\n2.000,b,2\n"
+        );
+    }
+}
@@ -0,0 +1,484 @@
+//! Writing captured records as CSV, text, JSON Lines, raw bytes or HTML.
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ansi::{self, AnsiMode},
+    highlight::{HighlightRule, Highlighter},
+    plot::escape_xml,
+    record::{Direction, Record},
+    styled,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Format {
+    /// One row per record: timestamp, direction, port, hex and text.
+    #[default]
+    CsvEvents,
+    /// The original layout: one column per console, one frame per cell.
+    CsvColumns,
+    Text,
+    TimestampedText,
+    JsonLines,
+    /// Received bytes exactly as they came off the port.
+    RawRx,
+    Html,
+}
+
+impl Format {
+    pub const ALL: [Format; 7] = [
+        Format::CsvEvents,
+        Format::CsvColumns,
+        Format::Text,
+        Format::TimestampedText,
+        Format::JsonLines,
+        Format::RawRx,
+        Format::Html,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::CsvEvents => "CSV event log",
+            Format::CsvColumns => "CSV columns",
+            Format::Text => "Text",
+            Format::TimestampedText => "Text with timestamps",
+            Format::JsonLines => "JSON Lines",
+            Format::RawRx => "Raw RX bytes",
+            Format::Html => "HTML",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::CsvEvents | Format::CsvColumns => "csv",
+            Format::Text | Format::TimestampedText => "txt",
+            Format::JsonLines => "jsonl",
+            Format::RawRx => "bin",
+            Format::Html => "html",
+        }
+    }
+
+    /// Picks the format for a file saved as `extension`, preferring `self`
+    /// when it fits so the CSV and text variants stay distinguishable.
+    pub fn for_extension(self, extension: &str) -> Format {
+        if self.extension().eq_ignore_ascii_case(extension) {
+            return self;
+        }
+        Format::ALL
+            .into_iter()
+            .find(|f| f.extension().eq_ignore_ascii_case(extension))
+            .unwrap_or(self)
+    }
+}
+
+/// Describes where and how a capture was made, for traceability. Written as
+/// `#` comment lines ahead of CSV and text, as a `{"metadata": ...}` object
+/// in JSON Lines and as a comment in HTML. Raw captures have no room for it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Metadata {
+    pub app_version: String,
+    pub port: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+    pub baud_rate: u32,
+    pub line_settings: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rx_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_bytes: Option<u64>,
+}
+
+impl Metadata {
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let mut res = vec![
+            ("app_version", self.app_version.clone()),
+            ("port", self.port.clone()),
+        ];
+        let optional = [
+            ("vid", self.vid.clone()),
+            ("pid", self.pid.clone()),
+            ("serial_number", self.serial_number.clone()),
+            ("baud_rate", Some(self.baud_rate.to_string())),
+            ("line_settings", Some(self.line_settings.clone())),
+            ("start", self.start.clone()),
+            ("end", self.end.clone()),
+            ("rx_bytes", self.rx_bytes.map(|n| n.to_string())),
+            ("tx_bytes", self.tx_bytes.map(|n| n.to_string())),
+        ];
+        res.extend(optional.into_iter().filter_map(|(k, v)| Some((k, v?))));
+        res
+    }
+
+    /// Fills in the time span and byte counts covered by `records`.
+    pub fn with_records(mut self, records: &[Record]) -> Self {
+        self.start = records.first().map(Record::timestamp);
+        self.end = records.last().map(Record::timestamp);
+        let count = |d: Direction| {
+            records
+                .iter()
+                .filter(|r| r.direction == d)
+                .map(|r| r.data.len() as u64)
+                .sum()
+        };
+        self.rx_bytes = Some(count(Direction::Rx));
+        self.tx_bytes = Some(count(Direction::Tx));
+        self
+    }
+
+    /// Writes the block in the form `format` allows.
+    pub fn write<W: Write>(&self, format: Format, mut writer: W) -> io::Result<()> {
+        match format {
+            Format::CsvEvents | Format::CsvColumns | Format::Text | Format::TimestampedText => {
+                for (key, value) in self.entries() {
+                    writeln!(writer, "# {key}: {value}")?;
+                }
+                Ok(())
+            }
+            Format::JsonLines => {
+                #[derive(Serialize)]
+                struct Header<'a> {
+                    metadata: &'a Metadata,
+                }
+                serde_json::to_writer(&mut writer, &Header { metadata: self })?;
+                writer.write_all(b"\n")
+            }
+            Format::RawRx => Ok(()),
+            Format::Html => {
+                writer.write_all(b"<!--\n")?;
+                for (key, value) in self.entries() {
+                    writeln!(writer, "{key}: {}", value.replace("--", "- -"))?;
+                }
+                writer.write_all(b"-->\n")
+            }
+        }
+    }
+}
+
+/// Writes `records` in a record-based `format`, streaming them one at a
+/// time. The column layout is not record-based and is handled by the caller.
+pub fn write<W: Write>(
+    format: Format,
+    records: &[Record],
+    rules: &[HighlightRule],
+    metadata: &Metadata,
+    writer: W,
+) -> io::Result<()> {
+    let mut wtr = RecordWriter::new(format, rules, metadata, writer)?;
+    for r in records {
+        wtr.write(r)?;
+    }
+    wtr.finish(None).map(|_| ())
+}
+
+/// Incremental writer for the record-based formats, used both for downloads
+/// and for recording straight to disk.
+pub struct RecordWriter<W: Write> {
+    format: Format,
+    writer: W,
+    highlighter: Option<Highlighter>,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(
+        format: Format,
+        rules: &[HighlightRule],
+        metadata: &Metadata,
+        mut writer: W,
+    ) -> io::Result<Self> {
+        if format != Format::Html {
+            metadata.write(format, &mut writer)?;
+        }
+        match format {
+            Format::CsvEvents => {
+                let mut wtr = csv::Writer::from_writer(&mut writer);
+                wtr.write_record(["timestamp", "direction", "port", "hex", "text"])?;
+                wtr.flush()?;
+            }
+            Format::CsvColumns => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "the column layout is built from the consoles",
+                ))
+            }
+            Format::Html => {
+                writer.write_all(HTML_HEAD.as_bytes())?;
+                metadata.write(format, &mut writer)?;
+            }
+            _ => (),
+        }
+        let highlighter = (format == Format::Html).then(|| Highlighter::new(rules.to_vec()));
+        Ok(Self {
+            format,
+            writer,
+            highlighter,
+        })
+    }
+
+    pub fn write(&mut self, r: &Record) -> io::Result<()> {
+        let w = &mut self.writer;
+        match self.format {
+            Format::CsvEvents => write_event(r, w),
+            Format::CsvColumns => Ok(()),
+            Format::Text => w.write_all(&r.data),
+            Format::TimestampedText => write_timestamped(r, w),
+            Format::JsonLines => write_json(r, w),
+            Format::RawRx if r.direction == Direction::Rx => w.write_all(&r.data),
+            Format::RawRx => Ok(()),
+            Format::Html => write_html(r, self.highlighter.as_ref().unwrap(), w),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Closes any open document structure and returns the inner writer.
+    /// `trailer` adds a second metadata block at the end, for files whose
+    /// span and byte counts were not known when they were started.
+    pub fn finish(mut self, trailer: Option<&Metadata>) -> io::Result<W> {
+        if let Some(metadata) = trailer {
+            metadata.write(self.format, &mut self.writer)?;
+        }
+        if self.format == Format::Html {
+            self.writer.write_all(b"</body>\n</html>\n")?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+fn write_event<W: Write>(r: &Record, writer: W) -> io::Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record([
+        r.timestamp().as_str(),
+        r.direction.name(),
+        r.port.as_str(),
+        r.hex().as_str(),
+        &r.text(),
+    ])?;
+    wtr.flush()
+}
+
+/// Every record starts on a line of its own, prefixed with its time and
+/// direction.
+fn write_timestamped<W: Write>(r: &Record, mut writer: W) -> io::Result<()> {
+    writeln!(
+        writer,
+        "[{}] {} {}",
+        r.timestamp(),
+        r.direction.name(),
+        r.text().trim_end_matches(['\r', '\n'])
+    )
+}
+
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    timestamp: String,
+    direction: &'static str,
+    port: &'a str,
+    length: usize,
+    hex: String,
+    text: &'a str,
+}
+
+fn write_json<W: Write>(r: &Record, mut writer: W) -> io::Result<()> {
+    let text = r.text();
+    let record = JsonRecord {
+        timestamp: r.timestamp(),
+        direction: r.direction.name(),
+        port: &r.port,
+        length: r.data.len(),
+        hex: r.hex(),
+        text: &text,
+    };
+    serde_json::to_writer(&mut writer, &record)?;
+    writer.write_all(b"\n")
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Serial monitor log</title>
+<style>
+body { background: #212529; color: #dee2e6; font-family: monospace; font-size: 0.875rem; }
+.record { white-space: pre-wrap; }
+.time { color: #6c757d; }
+.tx { color: #6ea8fe; }
+</style>
+</head>
+<body>
+"#;
+
+/// Renders a record with ANSI colors and highlight rules applied, the same
+/// way the consoles show it.
+fn write_html<W: Write>(r: &Record, highlighter: &Highlighter, mut writer: W) -> io::Result<()> {
+    let class = match r.direction {
+        Direction::Rx => "record",
+        Direction::Tx => "record tx",
+    };
+    write!(
+        writer,
+        r#"<div class="{class}"><span class="time">[{}] {}</span> "#,
+        r.timestamp(),
+        r.direction.name()
+    )?;
+    let text = r.text();
+    for (i, line) in ansi::parse(&text, AnsiMode::Render).iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b"\n")?;
+        }
+        for p in styled::pieces(line, highlighter, &[]) {
+            let style = p.css(highlighter);
+            if style.is_empty() {
+                writer.write_all(escape_xml(p.text).as_bytes())?;
+            } else {
+                write!(
+                    writer,
+                    r#"<span style="{}">{}</span>"#,
+                    escape_xml(&style),
+                    escape_xml(p.text)
+                )?;
+            }
+        }
+    }
+    writer.write_all(b"</div>\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import;
+
+    fn metadata() -> Metadata {
+        Metadata {
+            app_version: "1.2.3".to_string(),
+            port: "/dev/ttyUSB0".to_string(),
+            vid: Some("0403".to_string()),
+            baud_rate: 115200,
+            line_settings: "8N1, no flow control".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn records() -> Vec<Record> {
+        vec![
+            Record::new(Direction::Tx, "/dev/ttyUSB0", b"AT\r\n".to_vec()),
+            Record::new(Direction::Rx, "/dev/ttyUSB0", vec![b'O', b'K', 0xff, b'\n']),
+        ]
+    }
+
+    fn export(format: Format) -> Vec<u8> {
+        let records = records();
+        let mut wtr = RecordWriter::new(format, &[], &metadata(), Vec::new()).unwrap();
+        for r in &records {
+            wtr.write(r).unwrap();
+        }
+        let trailer = metadata().with_records(&records);
+        wtr.finish(Some(&trailer)).unwrap()
+    }
+
+    fn expected_entries() -> Vec<(String, String)> {
+        metadata()
+            .with_records(&records())
+            .entries()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect()
+    }
+
+    #[test]
+    fn entries_skip_missing_values() {
+        let keys = metadata()
+            .entries()
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            ["app_version", "port", "vid", "baud_rate", "line_settings"]
+        );
+    }
+
+    #[test]
+    fn with_records_counts_bytes() {
+        let m = metadata().with_records(&records());
+        assert_eq!((m.rx_bytes, m.tx_bytes), (Some(4), Some(4)));
+        assert_eq!(m.start, Some(records()[0].timestamp()));
+        let empty = metadata().with_records(&[]);
+        assert_eq!((empty.start, empty.rx_bytes), (None, Some(0)));
+    }
+
+    #[test]
+    fn csv_round_trips() {
+        let capture =
+            import::parse_csv(&String::from_utf8(export(Format::CsvEvents)).unwrap()).unwrap();
+        assert_eq!(capture.metadata, expected_entries());
+        let data = capture.records.iter().map(|r| &r.data).collect::<Vec<_>>();
+        assert_eq!(data, records().iter().map(|r| &r.data).collect::<Vec<_>>());
+        assert_eq!(capture.records[0].direction, Direction::Tx);
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        let capture =
+            import::parse_json_lines(&String::from_utf8(export(Format::JsonLines)).unwrap())
+                .unwrap();
+        let mut metadata = capture.metadata;
+        let mut expected = expected_entries();
+        metadata.sort();
+        expected.sort();
+        assert_eq!(metadata, expected);
+        assert_eq!(capture.records.len(), 2);
+        assert_eq!(capture.records[1].data, records()[1].data);
+    }
+
+    #[test]
+    fn text_starts_with_comments() {
+        let text = export(Format::Text);
+        let text = String::from_utf8_lossy(&text);
+        assert!(text.starts_with("# app_version: 1.2.3\n# port: /dev/ttyUSB0\n"));
+        assert!(text.contains("AT\r\nOK"));
+        assert!(text.ends_with("# tx_bytes: 4\n"));
+    }
+
+    #[test]
+    fn html_comment_cannot_be_closed_early() {
+        let m = Metadata {
+            port: "a-->b".to_string(),
+            ..metadata()
+        };
+        let mut html = Vec::new();
+        m.write(Format::Html, &mut html).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.starts_with("<!--\n"));
+        assert!(html.ends_with("-->\n"));
+        assert_eq!(html.matches("-->").count(), 1);
+        assert!(html.contains("port: a- ->b\n"));
+    }
+
+    #[test]
+    fn raw_has_only_received_bytes() {
+        assert_eq!(export(Format::RawRx), b"OK\xff\n");
+    }
+
+    #[test]
+    fn column_layout_is_refused() {
+        let res = RecordWriter::new(Format::CsvColumns, &[], &metadata(), Vec::new());
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use regex::Regex;
+
+/// The most context lines the console offers.
+pub const MAX_CONTEXT: usize = 99;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    pub query: String,
+    pub regex: bool,
+    pub invert: bool,
+    pub context: usize,
+}
+
+impl Filter {
+    pub fn is_active(&self) -> bool {
+        !self.query.is_empty()
+    }
+
+    fn compile(&self) -> Result<Regex, regex::Error> {
+        let pattern = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        Regex::new(&pattern)
+    }
+
+    /// Returns the indices of the lines to show: those matching the query
+    /// (or not, when inverted) plus `context` lines on either side.
+    pub fn apply(&self, lines: &[&str]) -> Result<Vec<usize>, regex::Error> {
+        if !self.is_active() {
+            return Ok((0..lines.len()).collect());
+        }
+        let re = self.compile()?;
+        let mut shown = vec![false; lines.len()];
+        for (i, line) in lines.iter().enumerate() {
+            if re.is_match(line) != self.invert {
+                let start = i.saturating_sub(self.context);
+                let end = i
+                    .saturating_add(self.context)
+                    .saturating_add(1)
+                    .min(lines.len());
+                shown[start..end].iter_mut().for_each(|s| *s = true);
+            }
+        }
+        Ok((0..lines.len()).filter(|&i| shown[i]).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINES: [&str; 5] = ["boot", "ERROR disk", "ok", "ok", "error net"];
+
+    fn filter(query: &str) -> Filter {
+        Filter {
+            query: query.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn inactive_shows_everything() {
+        assert_eq!(Filter::default().apply(&LINES).unwrap(), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn plain_query_matches_literally() {
+        assert_eq!(filter("error").apply(&LINES).unwrap(), [4]);
+        assert_eq!(filter("o.").apply(&LINES).unwrap(), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn regex_and_invert() {
+        let f = Filter {
+            regex: true,
+            ..filter("(?i)^error")
+        };
+        assert_eq!(f.apply(&LINES).unwrap(), [1, 4]);
+        let f = Filter { invert: true, ..f };
+        assert_eq!(f.apply(&LINES).unwrap(), [0, 2, 3]);
+    }
+
+    #[test]
+    fn context_surrounds_matches() {
+        let f = Filter {
+            context: 1,
+            ..filter("ERROR")
+        };
+        assert_eq!(f.apply(&LINES).unwrap(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn huge_context_shows_everything() {
+        let f = Filter {
+            context: usize::MAX,
+            ..filter("ok")
+        };
+        assert_eq!(f.apply(&LINES).unwrap(), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        let f = Filter {
+            regex: true,
+            ..filter("(")
+        };
+        assert!(f.apply(&LINES).is_err());
+    }
+}
@@ -1,64 +1,182 @@
+//! The low-level port handle: a Tokio task that owns the serial stream and
+//! splits what it reads into frames at newlines or pauses.
+
+use chrono::{DateTime, Local};
 use log::*;
 
-use std::io::{Error, ErrorKind::BrokenPipe};
+use std::{
+    fmt,
+    io::{Error, ErrorKind::BrokenPipe},
+};
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, ReadHalf, WriteHalf},
+    io::{AsyncReadExt, AsyncWriteExt},
     sync::mpsc::{error::TryRecvError, unbounded_channel, UnboundedReceiver, UnboundedSender},
     task::JoinHandle,
+    time::{sleep, sleep_until, Duration, Instant},
+};
+use tokio_serial::{
+    DataBits, FlowControl, Parity, SerialPort, SerialPortBuilderExt, SerialStream, StopBits,
 };
-use tokio_serial::{SerialPortBuilderExt, SerialStream};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Framing and flow control. Defaults to 8N1 without flow control.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineSettings {
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+}
+
+impl Default for LineSettings {
+    fn default() -> Self {
+        Self {
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+        }
+    }
+}
+
+/// The usual shorthand, e.g. "8N1, no flow control".
+impl fmt::Display for LineSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data_bits = match self.data_bits {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Odd => 'O',
+            Parity::Even => 'E',
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        let flow_control = match self.flow_control {
+            FlowControl::None => "no",
+            FlowControl::Software => "software",
+            FlowControl::Hardware => "hardware",
+        };
+        write!(
+            f,
+            "{data_bits}{parity}{stop_bits}, {flow_control} flow control"
+        )
+    }
+}
+
+const READ_BUF_SIZE: usize = 1024;
+// Unterminated data (e.g. a "login: " prompt) is passed on once the port has
+// been quiet for this long.
+const FRAME_GAP: Duration = Duration::from_millis(50);
+/// Frames are cut at this size, so a stream without newlines or pauses still
+/// gets passed on.
+const MAX_FRAME: usize = 4096;
+
+/// Received data, stamped with the time its first byte arrived.
+#[derive(Clone, Debug)]
+pub struct Chunk {
+    pub time: DateTime<Local>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+enum Request {
+    Write(Vec<u8>),
+    SetDtr(bool),
+    Break(Duration),
+    SubscribeRaw(UnboundedSender<Chunk>),
+}
+
+/// An open port. Dropping it closes the port.
 #[derive(Debug)]
 pub struct Handle {
-    write_channel: UnboundedSender<String>,
-    read_channel: UnboundedReceiver<String>,
+    request_channel: UnboundedSender<Request>,
+    read_channel: UnboundedReceiver<Chunk>,
     task_handles: Vec<JoinHandle<()>>,
 }
 
 impl Handle {
-    pub fn open(port: &str, br: u32) -> Result<Self> {
-        let handle = tokio_serial::new(port, br).open_native_async()?;
-        let (read_half, write_half) = tokio::io::split(handle);
-        let (tx_write, rx_write) = unbounded_channel();
+    pub fn open(port: &str, br: u32, settings: LineSettings) -> Result<Self> {
+        Self::spawn(port, br, settings, true)
+    }
+
+    /// Like `open`, but nothing is framed for `read`: received data only
+    /// goes to raw subscribers.
+    pub fn open_raw(port: &str, br: u32, settings: LineSettings) -> Result<Self> {
+        Self::spawn(port, br, settings, false)
+    }
+
+    fn spawn(port: &str, br: u32, settings: LineSettings, framed: bool) -> Result<Self> {
+        let handle = tokio_serial::new(port, br)
+            .data_bits(settings.data_bits)
+            .parity(settings.parity)
+            .stop_bits(settings.stop_bits)
+            .flow_control(settings.flow_control)
+            .open_native_async()?;
+        let (tx_request, rx_request) = unbounded_channel();
         let (tx_read, rx_read) = unbounded_channel();
-        let h1 = tokio::spawn(async move {
-            if let Err(e) = read_task(tx_read, read_half).await {
-                warn!("{:?}", e)
-            }
-        });
-        let h2 = tokio::spawn(async move {
-            if let Err(e) = write_task(rx_write, write_half).await {
+        let h = tokio::spawn(async move {
+            if let Err(e) = io_task(tx_read, framed, rx_request, handle).await {
                 warn!("{:?}", e)
             }
         });
         Ok(Self {
-            write_channel: tx_write,
+            request_channel: tx_request,
             read_channel: rx_read,
-            task_handles: vec![h1, h2],
+            task_handles: vec![h],
         })
     }
 
-    pub fn read(&mut self) -> Result<String> {
+    /// Returns the next received frame, or nothing if none is waiting.
+    pub fn read(&mut self) -> Result<Option<Chunk>> {
         match self.read_channel.try_recv() {
-            Ok(x) => Ok(x),
-            Err(TryRecvError::Empty) => Ok(String::new()),
+            Ok(x) => Ok(Some(x)),
+            Err(TryRecvError::Empty) => Ok(None),
             Err(TryRecvError::Disconnected) => Err(Error::new(BrokenPipe, "Handle disconnected")),
         }
     }
 
     #[must_use]
-    pub fn write(&self, content: &str) -> Result<()> {
-        self.write_channel
-            .send(content.to_string())
+    pub fn write_bytes(&self, content: &[u8]) -> Result<()> {
+        self.request(Request::Write(content.to_vec()))
+    }
+
+    /// A way to write to the port from another task.
+    pub fn writer(&self) -> Writer {
+        Writer(self.request_channel.clone())
+    }
+
+    pub fn set_dtr(&self, level: bool) -> Result<()> {
+        self.request(Request::SetDtr(level))
+    }
+
+    /// Sends everything read to `channel` as it arrives, without waiting for
+    /// the end of a frame, until the receiver is dropped.
+    pub fn subscribe_raw(&self, channel: UnboundedSender<Chunk>) -> Result<()> {
+        self.request(Request::SubscribeRaw(channel))
+    }
+
+    /// Holds the line in the break condition for `duration`.
+    pub fn send_break(&self, duration: Duration) -> Result<()> {
+        self.request(Request::Break(duration))
+    }
+
+    fn request(&self, request: Request) -> Result<()> {
+        self.request_channel
+            .send(request)
             .map_err(|_| Error::new(BrokenPipe, "Handle disconnected"))
     }
 
     #[must_use]
-    pub fn reconnect(&mut self, port: &str, br: u32) -> Result<()> {
+    pub fn reconnect(&mut self, port: &str, br: u32, settings: LineSettings) -> Result<()> {
         self.task_handles.iter().for_each(|h| h.abort());
-        *self = Handle::open(port, br)?;
+        *self = Handle::open(port, br, settings)?;
         Ok(())
     }
 
@@ -67,6 +185,18 @@ impl Handle {
     }
 }
 
+/// Writes to a port for as long as its `Handle` is kept.
+#[derive(Clone, Debug)]
+pub struct Writer(UnboundedSender<Request>);
+
+impl Writer {
+    pub fn write_bytes(&self, content: &[u8]) -> Result<()> {
+        self.0
+            .send(Request::Write(content.to_vec()))
+            .map_err(|_| Error::new(BrokenPipe, "Handle disconnected"))
+    }
+}
+
 impl Drop for Handle {
     fn drop(&mut self) {
         self.task_handles.iter().for_each(|h| h.abort());
@@ -74,32 +204,82 @@ impl Drop for Handle {
 }
 
 #[must_use]
-async fn read_task(channel: UnboundedSender<String>, handle: ReadHalf<SerialStream>) -> Result<()> {
-    let mut buf = String::new();
-    let mut reader = BufReader::new(handle);
-    while !channel.is_closed() {
-        debug!("reading");
-        let n = reader.read_line(&mut buf).await?;
-        if n == 0 {
-            break;
+async fn io_task(
+    read_channel: UnboundedSender<Chunk>,
+    framed: bool,
+    mut request_channel: UnboundedReceiver<Request>,
+    mut handle: SerialStream,
+) -> Result<()> {
+    let mut buf = [0; READ_BUF_SIZE];
+    let mut frame = Chunk {
+        time: Local::now(),
+        data: Vec::new(),
+    };
+    // Only received bytes move this, not writes or other requests.
+    let mut flush_at = Instant::now();
+    let mut raw: Vec<UnboundedSender<Chunk>> = Vec::new();
+    while !read_channel.is_closed() {
+        tokio::select! {
+            n = handle.read(&mut buf) => {
+                let n = n?;
+                if n == 0 {
+                    break;
+                }
+                let now = Local::now();
+                raw.retain(|s| {
+                    s.send(Chunk {
+                        time: now,
+                        data: buf[..n].to_vec(),
+                    })
+                    .is_ok()
+                });
+                if !framed {
+                    continue;
+                }
+                flush_at = Instant::now() + FRAME_GAP;
+                for &byte in &buf[..n] {
+                    if frame.data.is_empty() {
+                        frame.time = now;
+                    }
+                    frame.data.push(byte);
+                    if byte == b'\n' || frame.data.len() >= MAX_FRAME {
+                        send_frame(&read_channel, &mut frame)?;
+                    }
+                }
+            }
+            _ = sleep_until(flush_at), if !frame.data.is_empty() => send_frame(&read_channel, &mut frame)?,
+            request = request_channel.recv() => match request {
+                Some(Request::Write(msg)) => handle.write_all(&msg).await?,
+                // Not every adapter supports these, which is no reason to
+                // drop the connection.
+                Some(Request::SetDtr(level)) => {
+                    if let Err(e) = handle.write_data_terminal_ready(level) {
+                        warn!("Failed to set DTR: {:?}", e);
+                    }
+                }
+                Some(Request::Break(duration)) => match handle.set_break() {
+                    Ok(_) => {
+                        sleep(duration).await;
+                        if let Err(e) = handle.clear_break() {
+                            warn!("Failed to clear break: {:?}", e);
+                        }
+                    }
+                    Err(e) => warn!("Failed to send break: {:?}", e),
+                },
+                Some(Request::SubscribeRaw(channel)) => raw.push(channel),
+                None => break,
+            }
         }
-        channel
-            .send(buf.clone())
-            .map_err(|e| Error::new(BrokenPipe, e))?;
-        buf.clear();
     }
-    info!("Read task ended");
+    info!("IO task ended");
     Ok(())
 }
 
-#[must_use]
-async fn write_task(
-    mut channel: UnboundedReceiver<String>,
-    mut handle: WriteHalf<SerialStream>,
-) -> Result<()> {
-    while let Some(msg) = channel.recv().await {
-        handle.write_all(msg.as_bytes()).await?;
-    }
-    info!("Write task ended");
+fn send_frame(channel: &UnboundedSender<Chunk>, frame: &mut Chunk) -> Result<()> {
+    let chunk = Chunk {
+        time: frame.time,
+        data: std::mem::take(&mut frame.data),
+    };
+    channel.send(chunk).map_err(|e| Error::new(BrokenPipe, e))?;
     Ok(())
 }
@@ -0,0 +1,178 @@
+//! User-defined rules colouring matching text.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// How many lines' highlights are remembered before starting over.
+const CACHE_LINES: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HighlightRule {
+    pub pattern: String,
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+    pub bold: bool,
+    pub enabled: bool,
+}
+
+impl HighlightRule {
+    fn new(pattern: &str, foreground: &str, bold: bool) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            foreground: Some(foreground.to_string()),
+            background: None,
+            bold,
+            enabled: true,
+        }
+    }
+
+    pub fn style(&self) -> String {
+        let mut style = String::new();
+        if let Some(fg) = &self.foreground {
+            style.push_str(&format!("color: {fg};"));
+        }
+        if let Some(bg) = &self.background {
+            style.push_str(&format!("background-color: {bg};"));
+        }
+        if self.bold {
+            style.push_str("font-weight: bold;");
+        }
+        style
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct HighlightRules(pub Vec<HighlightRule>);
+
+impl Default for HighlightRules {
+    fn default() -> Self {
+        Self(vec![
+            HighlightRule::new(r"(?i)\b(error|err|fatal|panicked|panic)\b", "#ff6b6b", true),
+            HighlightRule::new(r"(?i)\b(warn|warning)\b", "#ffc107", true),
+            HighlightRule::new(r"\bINFO\b", "#6ea8fe", false),
+            HighlightRule::new(r"(?i)\b(debug|trace)\b", "#adb5bd", false),
+        ])
+    }
+}
+
+#[derive(Debug)]
+pub struct Highlighter {
+    rules: Vec<HighlightRule>,
+    regexes: Vec<Option<Regex>>,
+    /// The owners of lines already seen, as consoles draw the same lines
+    /// over and over.
+    cache: Mutex<HashMap<String, Vec<Option<usize>>>>,
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Self::new(HighlightRules::default().0)
+    }
+}
+
+impl Highlighter {
+    pub fn new(rules: Vec<HighlightRule>) -> Self {
+        let regexes = rules.iter().map(|r| Regex::new(&r.pattern).ok()).collect();
+        Self {
+            rules,
+            regexes,
+            cache: Mutex::default(),
+        }
+    }
+
+    pub fn rules(&self) -> &[HighlightRule] {
+        &self.rules
+    }
+
+    pub fn is_valid(&self, index: usize) -> bool {
+        matches!(self.regexes.get(index), Some(Some(_)))
+    }
+
+    pub fn set_rules(&mut self, rules: Vec<HighlightRule>) {
+        *self = Self::new(rules);
+    }
+
+    /// Returns, for every byte of `line`, the first enabled rule matching
+    /// it. Earlier rules win where matches overlap.
+    pub fn owners(&self, line: &str) -> Vec<Option<usize>> {
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(owner) = cache.get(line) {
+            return owner.clone();
+        }
+        let owner = self.find_owners(line);
+        if cache.len() >= CACHE_LINES {
+            cache.clear();
+        }
+        cache.insert(line.to_string(), owner.clone());
+        owner
+    }
+
+    fn find_owners(&self, line: &str) -> Vec<Option<usize>> {
+        let mut owner: Vec<Option<usize>> = vec![None; line.len()];
+        for (i, (rule, re)) in self.rules.iter().zip(&self.regexes).enumerate() {
+            let Some(re) = re.as_ref().filter(|_| rule.enabled) else {
+                continue;
+            };
+            for m in re.find_iter(line) {
+                owner[m.range()]
+                    .iter_mut()
+                    .filter(|o| o.is_none())
+                    .for_each(|o| *o = Some(i));
+            }
+        }
+        owner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str) -> HighlightRule {
+        HighlightRule::new(pattern, "red", false)
+    }
+
+    #[test]
+    fn earlier_rules_win_overlaps() {
+        let highlighter = Highlighter::new(vec![rule("bc"), rule("abcd")]);
+        assert_eq!(
+            highlighter.owners("xabcdx"),
+            [None, Some(1), Some(0), Some(0), Some(1), None]
+        );
+    }
+
+    #[test]
+    fn disabled_and_invalid_rules_are_skipped() {
+        let disabled = HighlightRule {
+            enabled: false,
+            ..rule("a")
+        };
+        let highlighter = Highlighter::new(vec![disabled, rule("("), rule("a")]);
+        assert!(!highlighter.is_valid(1));
+        assert_eq!(highlighter.owners("ab"), [Some(2), None]);
+    }
+
+    #[test]
+    fn new_rules_clear_the_cache() {
+        let mut highlighter = Highlighter::new(vec![rule("a")]);
+        assert_eq!(highlighter.owners("ab"), [Some(0), None]);
+        assert_eq!(highlighter.owners("ab"), [Some(0), None]);
+        highlighter.set_rules(vec![rule("b")]);
+        assert_eq!(highlighter.owners("ab"), [None, Some(0)]);
+    }
+
+    #[test]
+    fn styles() {
+        let rule = HighlightRule {
+            background: Some("black".to_string()),
+            ..HighlightRule::new("x", "red", true)
+        };
+        assert_eq!(
+            rule.style(),
+            "color: red;background-color: black;font-weight: bold;"
+        );
+    }
+}
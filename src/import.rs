@@ -0,0 +1,213 @@
+//! Reading exported CSV and JSON Lines logs back in.
+
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Local};
+use serde_json::Value;
+
+use crate::record::{Direction, Record};
+
+/// A log exported by the app, read back in.
+#[derive(Clone, Debug, Default)]
+pub struct Capture {
+    pub metadata: Vec<(String, String)>,
+    pub records: Vec<Record>,
+}
+
+impl Capture {
+    /// Seconds between the first and last record.
+    pub fn duration(&self) -> f64 {
+        match (self.records.first(), self.records.last()) {
+            (Some(first), Some(last)) => seconds_between(first, last),
+            _ => 0.0,
+        }
+    }
+}
+
+pub fn seconds_between(from: &Record, to: &Record) -> f64 {
+    (to.time - from.time).num_milliseconds().max(0) as f64 / 1000.0
+}
+
+/// Reads a CSV event log or JSON Lines export, chosen by extension.
+pub fn load(path: &Path) -> Result<Capture> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(e) if e.eq_ignore_ascii_case("jsonl") => parse_json_lines(&content),
+        Some(e) if e.eq_ignore_ascii_case("csv") => parse_csv(&content),
+        _ => bail!("Only CSV event logs and JSON Lines exports can be opened"),
+    }
+}
+
+pub fn parse_csv(content: &str) -> Result<Capture> {
+    // Recordings repeat the block at the end with their totals.
+    let head = content.lines().take_while(|l| l.starts_with('#'));
+    let mut tail = content
+        .lines()
+        .rev()
+        .take_while(|l| l.starts_with('#'))
+        .collect::<Vec<_>>();
+    tail.reverse();
+    let mut metadata: Vec<(String, String)> = Vec::new();
+    for (k, v) in head.chain(tail).filter_map(|l| l[1..].split_once(':')) {
+        let (k, v) = (k.trim().to_string(), v.trim().to_string());
+        match metadata.iter_mut().find(|(key, _)| *key == k) {
+            Some(entry) => entry.1 = v,
+            None => metadata.push((k, v)),
+        }
+    }
+    let mut rdr = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .from_reader(content.as_bytes());
+    let headers = rdr.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| anyhow!("Not an event log: no {name} column"))
+    };
+    let (time, direction, port) = (column("timestamp")?, column("direction")?, column("port")?);
+    let (hex, text) = (column("hex").ok(), column("text").ok());
+
+    let mut records = Vec::new();
+    for (i, row) in rdr.records().enumerate() {
+        let row = row?;
+        let field = |c: usize| row.get(c).unwrap_or_default();
+        let data = match (hex, text) {
+            (Some(c), _) if !field(c).is_empty() => parse_hex(field(c)),
+            (_, Some(c)) => Ok(field(c).as_bytes().to_vec()),
+            _ => Ok(Vec::new()),
+        };
+        records.push(
+            record(field(time), field(direction), field(port), data)
+                .with_context(|| format!("Row {}", i + 1))?,
+        );
+    }
+    Ok(Capture { metadata, records })
+}
+
+pub fn parse_json_lines(content: &str) -> Result<Capture> {
+    let mut capture = Capture::default();
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(line).with_context(|| format!("Line {}", i + 1))?;
+        if let Some(Value::Object(meta)) = value.get("metadata") {
+            for (k, v) in meta {
+                let v = match v {
+                    Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                };
+                match capture.metadata.iter_mut().find(|(key, _)| key == k) {
+                    Some(entry) => entry.1 = v,
+                    None => capture.metadata.push((k.clone(), v)),
+                }
+            }
+            continue;
+        }
+        let str_field = |name: &str| value.get(name).and_then(Value::as_str).unwrap_or_default();
+        let data = match value.get("hex").and_then(Value::as_str) {
+            Some(hex) if !hex.is_empty() => parse_hex(hex),
+            _ => Ok(str_field("text").as_bytes().to_vec()),
+        };
+        capture.records.push(
+            record(
+                str_field("timestamp"),
+                str_field("direction"),
+                str_field("port"),
+                data,
+            )
+            .with_context(|| format!("Line {}", i + 1))?,
+        );
+    }
+    Ok(capture)
+}
+
+fn record(time: &str, direction: &str, port: &str, data: Result<Vec<u8>>) -> Result<Record> {
+    let time = DateTime::parse_from_rfc3339(time)
+        .with_context(|| format!("Bad timestamp {time:?}"))?
+        .with_timezone(&Local);
+    let direction = match direction {
+        "RX" => Direction::Rx,
+        "TX" => Direction::Tx,
+        d => bail!("Bad direction {d:?}"),
+    };
+    Ok(Record {
+        time,
+        direction,
+        port: port.to_string(),
+        data: data?,
+    })
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>> {
+    hex.split_whitespace()
+        .map(|b| u8::from_str_radix(b, 16).with_context(|| format!("Bad hex byte {b:?}")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_with_metadata() {
+        let content = "\
+# port: /dev/ttyUSB0
+# records: 0
+timestamp,direction,port,hex,text
+2024-01-31T12:00:00.000+00:00,RX,/dev/ttyUSB0,68 69,ignored
+2024-01-31T12:00:01.500+00:00,TX,/dev/ttyUSB0,,ok
+# records: 2
+";
+        let capture = parse_csv(content).unwrap();
+        assert_eq!(
+            capture.metadata,
+            [
+                ("port".to_string(), "/dev/ttyUSB0".to_string()),
+                ("records".to_string(), "2".to_string()),
+            ]
+        );
+        assert_eq!(capture.records.len(), 2);
+        assert_eq!(capture.records[0].direction, Direction::Rx);
+        assert_eq!(capture.records[0].data, b"hi");
+        assert_eq!(capture.records[1].direction, Direction::Tx);
+        assert_eq!(capture.records[1].data, b"ok");
+        assert_eq!(capture.duration(), 1.5);
+    }
+
+    #[test]
+    fn csv_errors_name_the_row() {
+        let err = parse_csv("timestamp,direction,port\n2024-01-31T12:00:00Z,UP,p\n").unwrap_err();
+        assert_eq!(err.to_string(), "Row 1");
+        assert!(parse_csv("time,data\n").is_err());
+    }
+
+    #[test]
+    fn json_lines() {
+        let content = r#"{"metadata": {"port": "COM3", "baud": 9600}}
+{"timestamp": "2024-01-31T12:00:00Z", "direction": "RX", "port": "COM3", "text": "a"}
+
+{"timestamp": "2024-01-31T12:00:00Z", "direction": "TX", "port": "COM3", "hex": "00 ff"}
+"#;
+        let capture = parse_json_lines(content).unwrap();
+        let meta = |k: &str| {
+            capture
+                .metadata
+                .iter()
+                .find(|(key, _)| key == k)
+                .map(|e| &e.1)
+        };
+        assert_eq!(meta("port").unwrap(), "COM3");
+        assert_eq!(meta("baud").unwrap(), "9600");
+        assert_eq!(capture.records[0].data, b"a");
+        assert_eq!(capture.records[1].data, [0x00, 0xff]);
+    }
+
+    #[test]
+    fn bad_hex_is_an_error() {
+        let line = r#"{"timestamp": "2024-01-31T12:00:00Z", "direction": "RX", "hex": "zz"}"#;
+        assert!(parse_json_lines(line).is_err());
+    }
+}
@@ -0,0 +1,38 @@
+//! The core of Serial Monitor: finding ports, talking to them, and
+//! capturing and exporting what was sent and received. The desktop app,
+//! the terminal UI and the headless CLI are all built on it. Depend on the
+//! package with `default-features = false` to leave the desktop app and the
+//! terminal UI, and their dependencies, out.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use serial_monitor::{connection::Connection, ports};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let port = ports::get_available_usb().into_iter().next().expect("no ports");
+//! let mut connection = Connection::new(115_200);
+//! connection.open(port.name())?;
+//! connection.write("version\n")?;
+//! loop {
+//!     tokio::time::sleep(Duration::from_millis(20)).await;
+//!     print!("{}", connection.read()?);
+//! }
+//! # }
+//! ```
+//!
+//! See the `examples` directory for capturing to a file.
+
+pub mod ansi;
+pub mod connection;
+pub mod export;
+pub mod handle;
+pub mod highlight;
+pub mod import;
+pub mod plot;
+pub mod ports;
+pub mod record;
+pub mod recorder;
+// Shared with the app's consoles for rendering; not a stable API.
+#[doc(hidden)]
+pub mod styled;
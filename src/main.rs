@@ -1,17 +1,108 @@
 #![allow(non_snake_case)]
+#[cfg(feature = "gui")]
 mod api;
+#[cfg(feature = "gui")]
 mod app;
-mod handle;
-mod ports;
+#[cfg(feature = "gui")]
+mod bigfile;
+#[cfg(feature = "gui")]
+mod bridge;
+mod cli;
+#[cfg(feature = "gui")]
+mod control;
+#[cfg(feature = "gui")]
+mod dashboard;
+#[cfg(feature = "gui")]
+mod filter;
+#[cfg(feature = "gui")]
+mod responder;
+#[cfg(feature = "gui")]
+mod script;
+#[cfg(feature = "gui")]
+mod search;
+#[cfg(any(feature = "gui", feature = "tui"))]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+mod settings;
+#[cfg(feature = "gui")]
+mod sniffer;
+#[cfg(feature = "gui")]
+mod telnet;
+#[cfg(feature = "gui")]
+mod terminal;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "gui")]
+mod websocket;
+#[cfg(feature = "gui")]
 mod components {
     pub mod consoles;
+    pub mod dashboard_panel;
+    pub mod file_viewer;
+    pub mod highlight_panel;
     pub mod input_box;
+    pub mod log_panel;
+    pub mod network_panel;
+    pub mod panels;
+    pub mod plot_panel;
+    pub mod recording_panel;
+    pub mod responder_panel;
+    pub mod script_panel;
     pub mod selector_row;
+    pub mod sniffer_view;
+    pub mod terminal;
 }
 
+use std::{process::ExitCode, time::Duration};
+
+use clap::Parser;
 use env_logger::Env;
 
-fn main() {
-    env_logger::Builder::from_env(Env::default().default_filter_or("trace")).init();
-    dioxus_desktop::launch(app::App);
+use cli::{Cli, Command};
+
+/// How often ports are listed, and lost ones reconnected.
+#[cfg(any(feature = "gui", feature = "tui"))]
+const SCAN_FREQ: Duration = Duration::from_millis(500);
+/// How often an open port is read.
+const READ_FREQ: Duration = Duration::from_millis(20);
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match cli.command {
+        #[cfg(feature = "gui")]
+        None => {
+            env_logger::Builder::from_env(Env::default().default_filter_or("trace")).init();
+            let props = app::AppProps {
+                control: cli.control,
+            };
+            dioxus_desktop::launch_with_props(app::App, props, dioxus_desktop::Config::default());
+            ExitCode::SUCCESS
+        }
+        #[cfg(not(feature = "gui"))]
+        None => {
+            eprintln!("Built without the desktop app, see --help for the other modes");
+            ExitCode::from(2)
+        }
+        Some(Command::Headless(args)) => {
+            // Stdout carries the port's data, so only warnings go to stderr.
+            env_logger::Builder::from_env(Env::default().default_filter_or("warn")).init();
+            tokio::runtime::Runtime::new()
+                .expect("Failed to start the async runtime")
+                .block_on(cli::headless(args))
+        }
+        #[cfg(feature = "tui")]
+        Some(Command::Tui(args)) => {
+            // Anything logged to the terminal would tear the screen.
+            env_logger::Builder::from_env(Env::default().default_filter_or("off")).init();
+            let res = tokio::runtime::Runtime::new()
+                .expect("Failed to start the async runtime")
+                .block_on(tui::run(args));
+            match res {
+                Ok(_) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("{e}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,391 @@
+//! Extraction of numeric series from received lines.
+
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+pub const MAX_POINTS: usize = 20_000;
+pub const COLORS: [&str; 8] = [
+    "#0d6efd", "#fd7e14", "#20c997", "#d63384", "#ffc107", "#6f42c1", "#0dcaf0", "#dc3545",
+];
+
+/// Splits received frames into complete lines.
+#[derive(Debug, Default)]
+pub struct LineAssembler {
+    partial: String,
+}
+
+impl LineAssembler {
+    pub fn push(&mut self, data: &str) -> Vec<String> {
+        self.partial.push_str(data);
+        let mut lines = Vec::new();
+        while let Some(i) = self.partial.find('\n') {
+            let line: String = self.partial.drain(..=i).collect();
+            lines.push(line.trim_end().to_string());
+        }
+        lines
+    }
+}
+
+/// Extracts the numbers in `line`. `name:value` and `name=value` pairs keep
+/// their name; bare numbers are named by their position, starting at 1.
+pub fn parse_line(line: &str) -> Vec<(String, f64)> {
+    let mut res = Vec::new();
+    let mut position = 0;
+    for token in line
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter(|t| !t.is_empty())
+    {
+        match token.rsplit_once([':', '=']) {
+            Some((name, value)) if !name.is_empty() => {
+                if let Ok(v) = value.parse::<f64>() {
+                    res.push((name.to_string(), v));
+                }
+            }
+            _ => {
+                if let Ok(v) = token.parse::<f64>() {
+                    position += 1;
+                    res.push((position.to_string(), v));
+                }
+            }
+        }
+    }
+    res.retain(|(_, v)| v.is_finite());
+    res
+}
+
+#[derive(Debug)]
+pub struct Series {
+    pub name: String,
+    pub points: VecDeque<(f64, f64)>,
+    pub visible: bool,
+}
+
+#[derive(Debug)]
+pub struct Plot {
+    series: Vec<Series>,
+    lines: LineAssembler,
+    origin: Instant,
+    started: f64,
+}
+
+impl Default for Plot {
+    fn default() -> Self {
+        Self {
+            series: Vec::new(),
+            lines: LineAssembler::default(),
+            origin: Instant::now(),
+            started: unix_time(),
+        }
+    }
+}
+
+impl Plot {
+    pub fn series(&self) -> &[Series] {
+        &self.series
+    }
+
+    /// Seconds since the plot was started or last cleared.
+    pub fn now(&self) -> f64 {
+        self.origin.elapsed().as_secs_f64()
+    }
+
+    pub fn push(&mut self, data: &str) {
+        self.push_at(self.now(), data);
+    }
+
+    /// Like `push`, for data that arrived `t` seconds after the start.
+    pub fn push_at(&mut self, t: f64, data: &str) {
+        for line in self.lines.push(data) {
+            for (name, value) in parse_line(&line) {
+                self.push_value(t, &name, value);
+            }
+        }
+    }
+
+    fn push_value(&mut self, t: f64, name: &str, value: f64) {
+        let i = match self.series.iter().position(|s| s.name == name) {
+            Some(i) => i,
+            None => {
+                self.series.push(Series {
+                    name: name.to_string(),
+                    points: VecDeque::new(),
+                    visible: true,
+                });
+                self.series.len() - 1
+            }
+        };
+        let points = &mut self.series[i].points;
+        points.push_back((t, value));
+        if points.len() > MAX_POINTS {
+            points.pop_front();
+        }
+    }
+
+    pub fn toggle(&mut self, index: usize) {
+        if let Some(s) = self.series.get_mut(index) {
+            s.visible = !s.visible;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Clears the plot as if it had been started `elapsed` seconds ago, so a
+    /// loaded log of that length ends at the current time.
+    pub fn reset_to(&mut self, elapsed: f64) {
+        *self = Self::default();
+        let elapsed = Duration::from_secs_f64(elapsed.max(0.0));
+        self.origin = Instant::now().checked_sub(elapsed).unwrap_or(self.origin);
+        self.started -= elapsed.as_secs_f64();
+    }
+
+    fn value_range(&self, start: f64, end: f64) -> Option<(f64, f64)> {
+        self.series
+            .iter()
+            .filter(|s| s.visible)
+            .flat_map(|s| s.points.iter())
+            .filter(|(t, _)| (start..=end).contains(t))
+            .fold(None, |acc, &(_, v)| match acc {
+                None => Some((v, v)),
+                Some((lo, hi)) => Some((lo.min(v), hi.max(v))),
+            })
+    }
+}
+
+/// Seconds since the Unix epoch, as written to exported CSV files.
+pub fn unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default()
+}
+
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn color(index: usize) -> &'static str {
+    COLORS[index % COLORS.len()]
+}
+
+/// Round tick positions covering `lo..=hi`, roughly `count` of them.
+pub fn ticks(lo: f64, hi: f64, count: usize) -> Vec<f64> {
+    let span = hi - lo;
+    if span <= 0.0 || !span.is_finite() {
+        return vec![lo];
+    }
+    let raw = span / count as f64;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|s| *s >= raw)
+        .unwrap_or(10.0 * magnitude);
+    let first = (lo / step).ceil() as i64;
+    let last = (hi / step).floor() as i64;
+    (first..=last).map(|i| i as f64 * step).collect()
+}
+
+fn label(v: f64) -> String {
+    if v.abs() >= 1e5 || (v != 0.0 && v.abs() < 1e-3) {
+        format!("{v:.2e}")
+    } else {
+        let s = format!("{v:.3}");
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+pub const WIDTH: f64 = 600.0;
+pub const HEIGHT: f64 = 320.0;
+const MARGIN_LEFT: f64 = 56.0;
+const MARGIN_RIGHT: f64 = 10.0;
+const MARGIN_TOP: f64 = 10.0;
+const MARGIN_BOTTOM: f64 = 24.0;
+
+/// Renders the visible series between `start` and `end` (seconds) as an
+/// SVG document. The value axis is scaled to the data in view.
+pub fn render_svg(plot: &Plot, start: f64, end: f64) -> String {
+    let (lo, hi) = match plot.value_range(start, end) {
+        Some((lo, hi)) if hi > lo => {
+            let pad = (hi - lo) * 0.05;
+            (lo - pad, hi + pad)
+        }
+        Some((v, _)) => (v - 1.0, v + 1.0),
+        None => (0.0, 1.0),
+    };
+    let plot_w = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_h = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let x = |t: f64| MARGIN_LEFT + (t - start) / (end - start) * plot_w;
+    let y = |v: f64| MARGIN_TOP + (hi - v) / (hi - lo) * plot_h;
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {WIDTH} {HEIGHT}" width="100%" font-family="sans-serif" font-size="10">"#
+    );
+    let _ = write!(
+        svg,
+        r##"<rect x="0" y="0" width="{WIDTH}" height="{HEIGHT}" fill="#212529"/>"##
+    );
+    for v in ticks(lo, hi, 6) {
+        let py = y(v);
+        let _ = write!(
+            svg,
+            r##"<line x1="{MARGIN_LEFT}" y1="{py:.1}" x2="{:.1}" y2="{py:.1}" stroke="#495057" stroke-width="0.5"/><text x="{:.1}" y="{:.1}" fill="#adb5bd" text-anchor="end">{}</text>"##,
+            WIDTH - MARGIN_RIGHT,
+            MARGIN_LEFT - 4.0,
+            py + 3.0,
+            label(v)
+        );
+    }
+    for t in ticks(start, end, 6) {
+        let px = x(t);
+        let _ = write!(
+            svg,
+            r##"<line x1="{px:.1}" y1="{MARGIN_TOP}" x2="{px:.1}" y2="{:.1}" stroke="#495057" stroke-width="0.5"/><text x="{px:.1}" y="{:.1}" fill="#adb5bd" text-anchor="middle">{}s</text>"##,
+            HEIGHT - MARGIN_BOTTOM,
+            HEIGHT - MARGIN_BOTTOM + 14.0,
+            label(t)
+        );
+    }
+    let _ = write!(
+        svg,
+        r#"<clipPath id="plot-area"><rect x="{MARGIN_LEFT}" y="{MARGIN_TOP}" width="{plot_w}" height="{plot_h}"/></clipPath><g clip-path="url(#plot-area)" fill="none" stroke-width="1.5">"#
+    );
+    for (i, s) in plot.series.iter().enumerate().filter(|(_, s)| s.visible) {
+        // Include one point either side so lines run to the edges.
+        let first = s
+            .points
+            .partition_point(|(t, _)| *t < start)
+            .saturating_sub(1);
+        let last = (s.points.partition_point(|(t, _)| *t <= end) + 1).min(s.points.len());
+        let points = s
+            .points
+            .range(first..last)
+            .map(|&(t, v)| format!("{:.1},{:.1}", x(t), y(v)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let _ = write!(
+            svg,
+            r#"<polyline points="{points}" stroke="{}"/>"#,
+            color(i)
+        );
+    }
+    svg.push_str("</g>");
+    let _ = write!(
+        svg,
+        r##"<rect x="{MARGIN_LEFT}" y="{MARGIN_TOP}" width="{plot_w}" height="{plot_h}" fill="none" stroke="#6c757d"/>"##
+    );
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Same as `render_svg`, with a legend below the chart and a fixed pixel
+/// size so the file stands on its own outside the app.
+pub fn export_svg(plot: &Plot, start: f64, end: f64) -> String {
+    const ROW: f64 = 16.0;
+    let visible = plot
+        .series
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.visible)
+        .collect::<Vec<_>>();
+    let height = HEIGHT + ROW * visible.len() as f64 + 8.0;
+    let chart = render_svg(plot, start, end).replacen(
+        r#"width="100%""#,
+        &format!(r#"width="{WIDTH}" height="{HEIGHT}""#),
+        1,
+    );
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {WIDTH} {height}" width="{WIDTH}" height="{height}" font-family="sans-serif" font-size="11"><rect x="0" y="0" width="{WIDTH}" height="{height}" fill="#212529"/>{chart}"##
+    );
+    for (row, (i, s)) in visible.into_iter().enumerate() {
+        let y = HEIGHT + ROW * (row as f64 + 1.0);
+        let _ = write!(
+            svg,
+            r##"<rect x="{MARGIN_LEFT}" y="{:.1}" width="10" height="10" fill="{}"/><text x="{:.1}" y="{y:.1}" fill="#dee2e6">{}</text>"##,
+            y - 9.0,
+            color(i),
+            MARGIN_LEFT + 16.0,
+            escape_xml(&s.name)
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Writes the visible series between `start` and `end` as CSV, one row per
+/// sample time. Values received in the same line share a row.
+pub fn export_csv(plot: &Plot, start: f64, end: f64) -> csv::Result<String> {
+    let visible = plot.series.iter().filter(|s| s.visible).collect::<Vec<_>>();
+    let mut samples = visible
+        .iter()
+        .enumerate()
+        .flat_map(|(col, s)| {
+            s.points
+                .iter()
+                .filter(|(t, _)| (start..=end).contains(t))
+                .map(move |&(t, v)| (t, col, v))
+        })
+        .collect::<Vec<_>>();
+    samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    let mut header = vec!["unix_time".to_string(), "elapsed_s".to_string()];
+    header.extend(visible.iter().map(|s| s.name.clone()));
+    wtr.write_record(&header)?;
+    let mut i = 0;
+    while i < samples.len() {
+        let t = samples[i].0;
+        let mut row = vec![String::new(); header.len()];
+        row[0] = format!("{:.3}", plot.started + t);
+        row[1] = format!("{t:.3}");
+        while i < samples.len() && samples[i].0 == t {
+            row[samples[i].1 + 2] = samples[i].2.to_string();
+            i += 1;
+        }
+        wtr.write_record(&row)?;
+    }
+    let data = wtr.into_inner().map_err(|e| e.into_error())?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(values: &[(String, f64)]) -> Vec<(&str, f64)> {
+        values.iter().map(|(n, v)| (n.as_str(), *v)).collect()
+    }
+
+    #[test]
+    fn named_and_bare_values() {
+        let values = parse_line("temp:21.5, hum=40 3 -1e3; x");
+        assert_eq!(
+            names(&values),
+            [("temp", 21.5), ("hum", 40.0), ("1", 3.0), ("2", -1000.0)]
+        );
+    }
+
+    #[test]
+    fn skips_non_numbers() {
+        assert!(parse_line("a: b=x =1 ready").is_empty());
+        assert!(parse_line("inf NaN v=inf").is_empty());
+    }
+
+    #[test]
+    fn assembler_joins_lines() {
+        let mut lines = LineAssembler::default();
+        assert!(lines.push("1 2").is_empty());
+        assert_eq!(lines.push(" 3\r\n4\n5"), ["1 2 3", "4"]);
+    }
+}
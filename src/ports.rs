@@ -1,6 +1,9 @@
+//! Enumeration of the serial ports on this machine.
+
 use log::*;
 use tokio_serial::{self, SerialPortType};
 
+/// Lists every serial port, with USB details where the port has them.
 pub fn get_available_usb() -> Vec<PortInfo> {
     let mut res = Vec::new();
     match tokio_serial::available_ports() {
@@ -8,14 +11,19 @@ pub fn get_available_usb() -> Vec<PortInfo> {
             for p in ports {
                 debug!("{:?}", p);
                 let name = p.port_name;
-                let (manufacturer, product) = {
+                let (manufacturer, product, usb) = {
                     if let SerialPortType::UsbPort(info) = p.port_type {
-                        (info.manufacturer, info.product)
+                        let ids = UsbIds {
+                            vid: info.vid,
+                            pid: info.pid,
+                            serial_number: info.serial_number,
+                        };
+                        (info.manufacturer, info.product, Some(ids))
                     } else {
-                        (None, None)
+                        (None, None, None)
                     }
                 };
-                res.push(PortInfo::new(name, manufacturer, product))
+                res.push(PortInfo::new(name, manufacturer, product, usb))
             }
             res
         }
@@ -23,18 +31,38 @@ pub fn get_available_usb() -> Vec<PortInfo> {
     }
 }
 
+/// Looks up a port by name, e.g. to describe the port in use.
+pub fn find(name: &str) -> Option<PortInfo> {
+    get_available_usb().into_iter().find(|p| p.name == name)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UsbIds {
+    pub vid: u16,
+    pub pid: u16,
+    pub serial_number: Option<String>,
+}
+
+#[derive(Clone, Debug)]
 pub struct PortInfo {
     name: String,
     manufacturer: Option<String>,
     product: Option<String>,
+    usb: Option<UsbIds>,
 }
 
 impl PortInfo {
-    fn new(name: String, manufacturer: Option<String>, product: Option<String>) -> Self {
+    fn new(
+        name: String,
+        manufacturer: Option<String>,
+        product: Option<String>,
+        usb: Option<UsbIds>,
+    ) -> Self {
         Self {
             name,
             manufacturer,
             product,
+            usb,
         }
     }
 
@@ -55,4 +83,8 @@ impl PortInfo {
             .map(|s| s.as_str())
             .unwrap_or("Unknown")
     }
+
+    pub fn usb(&self) -> Option<&UsbIds> {
+        self.usb.as_ref()
+    }
 }
@@ -0,0 +1,65 @@
+//! Timestamped chunks of data sent or received.
+
+use std::borrow::Cow;
+
+use chrono::{DateTime, Local};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Rx,
+    Tx,
+}
+
+impl Direction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Rx => "RX",
+            Direction::Tx => "TX",
+        }
+    }
+}
+
+/// One chunk of traffic as it crossed the port, in either direction.
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub time: DateTime<Local>,
+    pub direction: Direction,
+    pub port: String,
+    pub data: Vec<u8>,
+}
+
+impl Record {
+    pub fn new(direction: Direction, port: &str, data: Vec<u8>) -> Self {
+        Self {
+            time: Local::now(),
+            direction,
+            port: port.to_string(),
+            data,
+        }
+    }
+
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.data)
+    }
+
+    /// Seconds since the Unix epoch, as used by the plot and dashboard.
+    pub fn unix_time(&self) -> f64 {
+        self.time.timestamp_millis() as f64 / 1000.0
+    }
+
+    pub fn timestamp(&self) -> String {
+        format_time(&self.time)
+    }
+
+    pub fn hex(&self) -> String {
+        self.data
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+pub fn format_time(time: &DateTime<Local>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.3f%:z").to_string()
+}
@@ -0,0 +1,411 @@
+//! Streaming records to rotating files on disk.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use flate2::{write::GzEncoder, Compression};
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    export::{Format, Metadata, RecordWriter},
+    highlight::HighlightRule,
+    record::{self, Direction, Record},
+};
+
+/// How often `Recorder::tick` should run. Buffered records are written out
+/// then, so a crash loses little more than the last second.
+pub const FLUSH_FREQ: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingSettings {
+    /// Defaults to a folder in the download directory.
+    pub directory: Option<PathBuf>,
+    /// File name without extension. `{port}`, `{date}`, `{time}` and `{n}`
+    /// (the rotation count) are substituted.
+    pub template: String,
+    pub format: Format,
+    pub max_size_mb: Option<u64>,
+    pub max_minutes: Option<u64>,
+    /// Compress files once they are rotated out.
+    pub gzip: bool,
+}
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            template: "{port}_{date}_{time}".to_string(),
+            format: Format::CsvEvents,
+            max_size_mb: None,
+            max_minutes: None,
+            gzip: false,
+        }
+    }
+}
+
+impl RecordingSettings {
+    pub fn directory(&self) -> Option<PathBuf> {
+        self.directory
+            .clone()
+            .or_else(|| Some(dirs::download_dir()?.join("serial-monitor")))
+    }
+
+    /// Expands the template for the `n`th file of a recording on `port`.
+    pub fn file_name(&self, port: &str, n: usize) -> String {
+        let port = Path::new(port)
+            .file_name()
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|| port.to_string())
+            .replace(
+                |c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '.',
+                "_",
+            );
+        let now = Local::now();
+        let name = self
+            .template
+            .replace("{port}", &port)
+            .replace("{date}", &now.format("%Y-%m-%d").to_string())
+            .replace("{time}", &now.format("%H%M%S").to_string())
+            .replace("{n}", &n.to_string());
+        format!("{name}.{}", self.format.extension())
+    }
+}
+
+/// Counts the bytes that pass through, for size-based rotation.
+struct Counting<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct Segment {
+    path: PathBuf,
+    writer: RecordWriter<Counting<BufWriter<File>>>,
+    opened: Instant,
+    metadata: Metadata,
+}
+
+/// Streams records to disk while it is alive, rotating files by size or age.
+pub struct Recorder {
+    settings: RecordingSettings,
+    rules: Vec<HighlightRule>,
+    metadata: Metadata,
+    directory: PathBuf,
+    segment: Option<Segment>,
+    count: usize,
+}
+
+impl Recorder {
+    /// Starts recording. `metadata` describes the port and heads every file;
+    /// the time span and byte counts of each file are appended when it is
+    /// closed.
+    pub fn start(
+        settings: RecordingSettings,
+        rules: Vec<HighlightRule>,
+        metadata: Metadata,
+    ) -> io::Result<Self> {
+        let directory = settings
+            .directory()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No download directory"))?;
+        fs::create_dir_all(&directory)?;
+        let mut recorder = Self {
+            settings,
+            rules,
+            metadata,
+            directory,
+            segment: None,
+            count: 0,
+        };
+        recorder.open()?;
+        Ok(recorder)
+    }
+
+    /// The file currently being written.
+    pub fn path(&self) -> Option<&Path> {
+        self.segment.as_ref().map(|s| s.path.as_path())
+    }
+
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        if self.is_due() {
+            self.rotate()?;
+        }
+        let Some(segment) = &mut self.segment else {
+            return Ok(());
+        };
+        let n = record.data.len() as u64;
+        let counter = match record.direction {
+            Direction::Rx => &mut segment.metadata.rx_bytes,
+            Direction::Tx => &mut segment.metadata.tx_bytes,
+        };
+        *counter = Some(counter.unwrap_or(0) + n);
+        segment.writer.write(record)
+    }
+
+    /// Flushes buffered records and rotates a file that is due even when
+    /// the port has gone quiet.
+    pub fn tick(&mut self) -> io::Result<()> {
+        if self.is_due() {
+            self.rotate()?;
+        }
+        match &mut self.segment {
+            Some(segment) => segment.writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn is_due(&self) -> bool {
+        let Some(segment) = &self.segment else {
+            return false;
+        };
+        let size = self
+            .settings
+            .max_size_mb
+            .is_some_and(|mb| segment.writer.get_ref().count >= mb.saturating_mul(1024 * 1024));
+        let age = self.settings.max_minutes.is_some_and(|min| {
+            segment.opened.elapsed() >= Duration::from_secs(min.saturating_mul(60))
+        });
+        size || age
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        self.count += 1;
+        let name = self.settings.file_name(&self.metadata.port, self.count);
+        let mut path = self.directory.join(&name);
+        let mut k = 1;
+        while path.exists() {
+            path = self.directory.join(format!("{k}-{name}"));
+            k += 1;
+        }
+        let file = Counting {
+            inner: BufWriter::new(File::create(&path)?),
+            count: 0,
+        };
+        info!("Recording to {}", path.display());
+        let metadata = Metadata {
+            start: Some(record::format_time(&Local::now())),
+            ..self.metadata.clone()
+        };
+        self.segment = Some(Segment {
+            writer: RecordWriter::new(self.settings.format, &self.rules, &metadata, file)?,
+            path,
+            opened: Instant::now(),
+            metadata,
+        });
+        Ok(())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        if let Some(segment) = self.segment.take() {
+            let trailer = Metadata {
+                end: Some(record::format_time(&Local::now())),
+                rx_bytes: Some(segment.metadata.rx_bytes.unwrap_or(0)),
+                tx_bytes: Some(segment.metadata.tx_bytes.unwrap_or(0)),
+                ..segment.metadata
+            };
+            segment.writer.finish(Some(&trailer))?;
+            info!("Recording {} closed", segment.path.display());
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let path = self.path().map(Path::to_owned);
+        self.close()?;
+        if let (Some(path), true) = (path, self.settings.gzip) {
+            thread::spawn(move || {
+                if let Err(e) = compress(&path) {
+                    error!("Failed to compress {}: {:?}", path.display(), e);
+                }
+            });
+        }
+        self.open()
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            error!("Failed to close recording: {:?}", e);
+        }
+    }
+}
+
+/// Replaces `path` with a gzipped copy next to it.
+fn compress(path: &Path) -> io::Result<()> {
+    let mut gz_path = path.as_os_str().to_owned();
+    gz_path.push(".gz");
+    let mut encoder = GzEncoder::new(
+        BufWriter::new(File::create(&gz_path)?),
+        Compression::default(),
+    );
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?.flush()?;
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    fn directory(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("recorder-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn start(dir: &Path, settings: RecordingSettings) -> Recorder {
+        let settings = RecordingSettings {
+            directory: Some(dir.to_owned()),
+            template: "log_{n}".to_string(),
+            format: Format::Text,
+            ..settings
+        };
+        let metadata = Metadata {
+            port: "/dev/ttyUSB0".to_string(),
+            ..Default::default()
+        };
+        Recorder::start(settings, Vec::new(), metadata).unwrap()
+    }
+
+    fn rx(data: Vec<u8>) -> Record {
+        Record::new(Direction::Rx, "/dev/ttyUSB0", data)
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn file_names() {
+        let settings = RecordingSettings {
+            template: "{port}-{n}".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(settings.file_name("/dev/ttyUSB0", 2), "ttyUSB0-2.csv");
+        assert_eq!(settings.file_name("COM3:x", 1), "COM3_x-1.csv");
+    }
+
+    #[test]
+    fn rotates_by_size() {
+        let dir = directory("size");
+        let mut recorder = start(
+            &dir,
+            RecordingSettings {
+                max_size_mb: Some(1),
+                ..Default::default()
+            },
+        );
+        let chunk = vec![b'x'; 64 * 1024];
+        // The header and a full megabyte of data: the next write is due.
+        for _ in 0..16 {
+            recorder.write(&rx(chunk.clone())).unwrap();
+        }
+        assert_eq!(files(&dir), ["log_1.txt"]);
+        recorder.write(&rx(b"next".to_vec())).unwrap();
+        drop(recorder);
+        assert_eq!(files(&dir), ["log_1.txt", "log_2.txt"]);
+        let second = fs::read_to_string(dir.join("log_2.txt")).unwrap();
+        assert!(second.contains("\nnext# app_version: "));
+        assert!(second.ends_with("# rx_bytes: 4\n# tx_bytes: 0\n"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotates_by_age() {
+        let dir = directory("age");
+        let mut recorder = start(
+            &dir,
+            RecordingSettings {
+                max_minutes: Some(1),
+                ..Default::default()
+            },
+        );
+        recorder.tick().unwrap();
+        assert_eq!(files(&dir), ["log_1.txt"]);
+        let segment = recorder.segment.as_mut().unwrap();
+        segment.opened = Instant::now().checked_sub(Duration::from_secs(61)).unwrap();
+        recorder.tick().unwrap();
+        assert_eq!(files(&dir), ["log_1.txt", "log_2.txt"]);
+        drop(recorder);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn trailer_counts_bytes() {
+        let dir = directory("trailer");
+        let mut recorder = start(&dir, RecordingSettings::default());
+        recorder.write(&rx(b"hello\n".to_vec())).unwrap();
+        recorder
+            .write(&Record::new(Direction::Tx, "/dev/ttyUSB0", b"hi".to_vec()))
+            .unwrap();
+        drop(recorder);
+        let text = fs::read_to_string(dir.join("log_1.txt")).unwrap();
+        let (head, tail) = text.split_once("hello\nhi").unwrap();
+        assert!(head.starts_with("# app_version: \n# port: /dev/ttyUSB0\n"));
+        assert!(head.contains("# start: "));
+        assert!(tail.contains("# end: "));
+        assert!(tail.ends_with("# rx_bytes: 6\n# tx_bytes: 2\n"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotated_files_are_compressed() {
+        let dir = directory("gzip");
+        let mut recorder = start(
+            &dir,
+            RecordingSettings {
+                max_minutes: Some(1),
+                gzip: true,
+                ..Default::default()
+            },
+        );
+        recorder.write(&rx(b"first".to_vec())).unwrap();
+        recorder.segment.as_mut().unwrap().opened =
+            Instant::now().checked_sub(Duration::from_secs(61)).unwrap();
+        recorder.tick().unwrap();
+        let gz = dir.join("log_1.txt.gz");
+        for _ in 0..100 {
+            if !dir.join("log_1.txt").exists() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(files(&dir), ["log_1.txt.gz", "log_2.txt"]);
+        let mut text = String::new();
+        GzDecoder::new(File::open(gz).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert!(text.contains("\nfirst# app_version: "));
+        assert!(text.ends_with("# rx_bytes: 5\n# tx_bytes: 0\n"));
+        drop(recorder);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
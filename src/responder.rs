@@ -0,0 +1,238 @@
+use std::time::Duration;
+
+use regex::bytes::Regex;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub pattern: String,
+    pub response: String,
+    pub delay: Duration,
+    pub repeat: bool,
+    pub enabled: bool,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            pattern: String::new(),
+            response: String::new(),
+            delay: Duration::ZERO,
+            repeat: true,
+            enabled: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    rule: Rule,
+    regex: Option<Regex>,
+    fired: bool,
+}
+
+impl Entry {
+    fn new(rule: Rule) -> Self {
+        let regex = Regex::new(&rule.pattern).ok();
+        Self {
+            rule,
+            regex,
+            fired: false,
+        }
+    }
+
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.regex.is_some()
+    }
+
+    pub fn has_fired(&self) -> bool {
+        self.fired
+    }
+
+    fn is_armed(&self) -> bool {
+        self.rule.enabled && (self.rule.repeat || !self.fired)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Reply {
+    pub rule: String,
+    pub data: Vec<u8>,
+    pub delay: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct Responder {
+    entries: Vec<Entry>,
+}
+
+impl Responder {
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn push(&mut self, rule: Rule) {
+        self.entries.push(Entry::new(rule));
+    }
+
+    pub fn update(&mut self, index: usize, rule: Rule) {
+        if let Some(e) = self.entries.get_mut(index) {
+            let fired = e.fired;
+            *e = Entry::new(rule);
+            e.fired = fired;
+        }
+    }
+
+    pub fn edit(&mut self, index: usize, f: impl FnOnce(&mut Rule)) {
+        if let Some(e) = self.entries.get(index) {
+            let mut rule = e.rule.clone();
+            f(&mut rule);
+            self.update(index, rule);
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.entries.len() {
+            self.entries.remove(index);
+        }
+    }
+
+    pub fn rearm(&mut self, index: usize) {
+        if let Some(e) = self.entries.get_mut(index) {
+            e.fired = false;
+        }
+    }
+
+    /// Returns the reply of the first armed rule matching `frame`. Capture
+    /// groups can be referenced in the response as `$1` or `${name}`.
+    pub fn respond(&mut self, frame: &str) -> Option<Reply> {
+        self.entries
+            .iter_mut()
+            .filter(|e| e.is_armed())
+            .find_map(|e| {
+                let caps = e.regex.as_ref()?.captures(frame.as_bytes())?;
+                let mut data = Vec::new();
+                caps.expand(&unescape(&e.rule.response), &mut data);
+                e.fired = true;
+                Some(Reply {
+                    rule: e.rule.name.clone(),
+                    data,
+                    delay: e.rule.delay,
+                })
+            })
+    }
+}
+
+/// Resolves `\r`, `\n`, `\t`, `\\` and `\xHH` escapes so control characters
+/// can be typed into a single-line response field. `\xHH` is the byte
+/// itself, not the character's UTF-8 encoding.
+pub fn unescape(s: &str) -> Vec<u8> {
+    let mut res = Vec::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            res.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('r') => res.push(b'\r'),
+            Some('n') => res.push(b'\n'),
+            Some('t') => res.push(b'\t'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(b) => res.push(b),
+                    Err(_) => {
+                        res.extend_from_slice(b"\\x");
+                        res.extend_from_slice(hex.as_bytes());
+                    }
+                }
+            }
+            Some(c) => {
+                let mut buf = [0; 4];
+                res.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+            None => res.push(b'\\'),
+        }
+    }
+    res
+}
+
+/// The reverse of `unescape`, for showing data on one line.
+pub fn escape(data: &[u8]) -> String {
+    let mut res = String::with_capacity(data.len());
+    for &b in data {
+        match b {
+            b'\r' => res.push_str("\\r"),
+            b'\n' => res.push_str("\\n"),
+            b'\t' => res.push_str("\\t"),
+            b'\\' => res.push_str("\\\\"),
+            b' '..=b'~' => res.push(b as char),
+            _ => res.push_str(&format!("\\x{b:02x}")),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescape_resolves_escapes() {
+        assert_eq!(unescape(r"a\r\n\tb\\"), b"a\r\n\tb\\");
+        assert_eq!(unescape(r"\x00\xff\x7F"), [0x00, 0xff, 0x7f]);
+        assert_eq!(unescape("é"), "é".as_bytes());
+    }
+
+    #[test]
+    fn unescape_keeps_what_it_does_not_know() {
+        assert_eq!(unescape(r"\q"), b"q");
+        assert_eq!(unescape(r"\xzz"), br"\xzz");
+        assert_eq!(unescape(r"\x4g"), br"\x4g");
+        assert_eq!(unescape("end\\"), b"end\\");
+    }
+
+    #[test]
+    fn escape_round_trips() {
+        let all = (0..=255).collect::<Vec<u8>>();
+        assert_eq!(unescape(&escape(&all)), all);
+        assert_eq!(escape(b"ok\r\n\x1b"), r"ok\r\n\x1b");
+    }
+
+    #[test]
+    fn respond_expands_captures() {
+        let mut responder = Responder::default();
+        responder.push(Rule {
+            name: "ping".to_string(),
+            pattern: r"PING (?<n>\d+)".to_string(),
+            response: r"PONG ${n}\r\n".to_string(),
+            ..Default::default()
+        });
+        let reply = responder.respond("PING 42").unwrap();
+        assert_eq!(reply.rule, "ping");
+        assert_eq!(reply.data, b"PONG 42\r\n");
+        assert_eq!(responder.respond("nothing"), None);
+    }
+
+    #[test]
+    fn one_shot_rules_fire_once() {
+        let mut responder = Responder::default();
+        responder.push(Rule {
+            pattern: "boot".to_string(),
+            response: "y".to_string(),
+            repeat: false,
+            ..Default::default()
+        });
+        assert!(responder.respond("boot").is_some());
+        assert!(responder.respond("boot").is_none());
+        responder.rearm(0);
+        assert!(responder.respond("boot").is_some());
+    }
+}
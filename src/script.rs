@@ -0,0 +1,325 @@
+use std::{
+    cell::RefCell,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::*;
+use regex::Regex;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, FLOAT, INT};
+use tokio::{
+    runtime,
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    time::timeout,
+};
+
+pub const DEFAULT_EXPECT_TIMEOUT: Duration = Duration::from_secs(2);
+const POLL_FREQ: Duration = Duration::from_millis(50);
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+#[derive(Debug)]
+pub enum ScriptEvent {
+    Send(String),
+    SetDtr(bool),
+    Log(String),
+    Finished(Result<(), String>),
+}
+
+pub struct Script {
+    stop: Arc<AtomicBool>,
+}
+
+impl Script {
+    /// Runs `source` on a dedicated thread. Port data arrives through
+    /// `received`; everything the script does is reported through `events`,
+    /// ending with exactly one `ScriptEvent::Finished`.
+    pub fn start(
+        source: String,
+        received: UnboundedReceiver<String>,
+        events: UnboundedSender<ScriptEvent>,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let rt = runtime::Handle::current();
+        thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let session = Session {
+                    rt,
+                    received,
+                    pending: String::new(),
+                    stop: stop.clone(),
+                };
+                // Finished must be sent even if a binding panics.
+                let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                    build_engine(session, events.clone(), stop)
+                        .run(&source)
+                        .map_err(|e| e.to_string())
+                }))
+                .unwrap_or_else(|_| Err("script panicked".to_string()));
+                info!("Script finished: {:?}", res);
+                let _ = events.send(ScriptEvent::Finished(res));
+            }
+        });
+        Self { stop }
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Script {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct Session {
+    rt: runtime::Handle,
+    received: UnboundedReceiver<String>,
+    pending: String,
+    stop: Arc<AtomicBool>,
+}
+
+impl Session {
+    fn expect(&mut self, pattern: &str, limit: Duration) -> ScriptResult<Array> {
+        let re = Regex::new(pattern).map_err(|e| e.to_string())?;
+        let deadline = deadline(limit)?;
+        loop {
+            if let Some(caps) = re.captures(&self.pending) {
+                let res = caps
+                    .iter()
+                    .map(|m| Dynamic::from(m.map(|m| m.as_str()).unwrap_or("").to_string()))
+                    .collect();
+                let end = caps.get(0).unwrap().end();
+                self.pending.drain(..end);
+                return Ok(res);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(format!("expect timed out waiting for /{pattern}/").into());
+            }
+            self.check_stopped()?;
+            // The timer must be made inside the runtime, not on this thread.
+            let received = &mut self.received;
+            match self
+                .rt
+                .block_on(async { timeout(remaining.min(POLL_FREQ), received.recv()).await })
+            {
+                Ok(Some(data)) => self.pending.push_str(&data),
+                Ok(None) => return Err("connection closed".into()),
+                Err(_) => (),
+            }
+        }
+    }
+
+    fn sleep(&self, duration: Duration) -> ScriptResult<()> {
+        let deadline = deadline(duration)?;
+        loop {
+            self.check_stopped()?;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(());
+            }
+            thread::sleep(remaining.min(POLL_FREQ));
+        }
+    }
+
+    fn check_stopped(&self) -> ScriptResult<()> {
+        if self.stop.load(Ordering::Relaxed) {
+            Err("script stopped".into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn deadline(after: Duration) -> ScriptResult<Instant> {
+    Instant::now()
+        .checked_add(after)
+        .ok_or_else(|| format!("invalid duration {after:?}").into())
+}
+
+/// Negative durations are no wait at all; NaN, infinite and huge ones are
+/// errors.
+fn seconds(x: FLOAT) -> ScriptResult<Duration> {
+    Duration::try_from_secs_f64(if x < 0.0 { 0.0 } else { x })
+        .map_err(|_| format!("invalid duration {x}").into())
+}
+
+fn build_engine(
+    session: Session,
+    events: UnboundedSender<ScriptEvent>,
+    stop: Arc<AtomicBool>,
+) -> Engine {
+    let session = Rc::new(RefCell::new(session));
+    let mut engine = Engine::new();
+
+    engine.on_progress(move |_| {
+        if stop.load(Ordering::Relaxed) {
+            Some("script stopped".into())
+        } else {
+            None
+        }
+    });
+
+    let tx = events.clone();
+    engine.on_print(move |s| {
+        let _ = tx.send(ScriptEvent::Log(s.to_string()));
+    });
+    let tx = events.clone();
+    engine.register_fn("log", move |s: &str| {
+        let _ = tx.send(ScriptEvent::Log(s.to_string()));
+    });
+
+    let tx = events.clone();
+    engine.register_fn("send", move |s: &str| {
+        let _ = tx.send(ScriptEvent::Send(s.to_string()));
+    });
+    let tx = events.clone();
+    engine.register_fn("send_line", move |s: &str| {
+        let _ = tx.send(ScriptEvent::Send(format!("{s}\n")));
+    });
+    let tx = events;
+    engine.register_fn("set_dtr", move |level: bool| {
+        let _ = tx.send(ScriptEvent::SetDtr(level));
+    });
+
+    let s = session.clone();
+    engine.register_fn("expect", move |pattern: &str| {
+        s.borrow_mut().expect(pattern, DEFAULT_EXPECT_TIMEOUT)
+    });
+    let s = session.clone();
+    engine.register_fn("expect", move |pattern: &str, limit: FLOAT| {
+        s.borrow_mut().expect(pattern, seconds(limit)?)
+    });
+    let s = session.clone();
+    engine.register_fn("expect", move |pattern: &str, limit: INT| {
+        s.borrow_mut().expect(pattern, seconds(limit as FLOAT)?)
+    });
+
+    let s = session.clone();
+    engine.register_fn("sleep", move |limit: FLOAT| {
+        s.borrow().sleep(seconds(limit)?)
+    });
+    let s = session;
+    engine.register_fn("sleep", move |limit: INT| {
+        s.borrow().sleep(seconds(limit as FLOAT)?)
+    });
+
+    engine.register_fn("assert", |cond: bool| -> ScriptResult<()> {
+        if cond {
+            Ok(())
+        } else {
+            Err("assertion failed".into())
+        }
+    });
+    engine.register_fn("assert", |cond: bool, msg: &str| -> ScriptResult<()> {
+        if cond {
+            Ok(())
+        } else {
+            Err(format!("assertion failed: {msg}").into())
+        }
+    });
+
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::*;
+
+    /// Runs `source` with `input` already received and returns what it did.
+    async fn run(source: &str, input: &[&str]) -> Vec<ScriptEvent> {
+        let (data, received) = unbounded_channel();
+        for s in input {
+            data.send(s.to_string()).unwrap();
+        }
+        let (tx, mut rx) = unbounded_channel();
+        let _script = Script::start(source.to_string(), received, tx);
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            let finished = matches!(event, ScriptEvent::Finished(_));
+            events.push(event);
+            if finished {
+                break;
+            }
+        }
+        drop(data);
+        events
+    }
+
+    fn error(events: &[ScriptEvent]) -> String {
+        match events.last() {
+            Some(ScriptEvent::Finished(Err(e))) => e.clone(),
+            other => panic!("expected an error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(seconds(1.5).unwrap(), Duration::from_millis(1500));
+        assert_eq!(seconds(-1.0).unwrap(), Duration::ZERO);
+        assert!(seconds(FLOAT::INFINITY).is_err());
+        assert!(seconds(FLOAT::NAN).is_err());
+        assert!(seconds(1e300).is_err());
+        assert!(deadline(Duration::MAX).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn expect_captures() {
+        let events = run(
+            r#"let m = expect("v(\\d+)\\.(\\d+)"); send_line(m[1] + "-" + m[2]);"#,
+            &["boot ", "v1.2\n"],
+        )
+        .await;
+        assert!(matches!(&events[0], ScriptEvent::Send(s) if s == "1-2\n"));
+        assert!(matches!(events[1], ScriptEvent::Finished(Ok(()))));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn expect_times_out() {
+        let start = Instant::now();
+        let events = run(r#"expect("ready", 0.1)"#, &["booting\n"]).await;
+        assert!(error(&events).contains("expect timed out waiting for /ready/"));
+        assert!(start.elapsed() < DEFAULT_EXPECT_TIMEOUT);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn bad_durations_fail() {
+        for source in [
+            "sleep(1e300)",
+            "sleep(1.0 / 0.0)",
+            r#"expect("x", 1e300)"#,
+            &format!("sleep({})", INT::MAX),
+        ] {
+            let events = run(source, &[]).await;
+            assert!(error(&events).contains("invalid duration"), "{source}");
+        }
+        // Negative durations are no wait at all.
+        let events = run(r#"sleep(-1); expect("", -5)"#, &[]).await;
+        assert!(matches!(events[0], ScriptEvent::Finished(Ok(()))));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stop_ends_sleep() {
+        let (_data, received) = unbounded_channel();
+        let (tx, mut rx) = unbounded_channel();
+        let script = Script::start("sleep(60)".to_string(), received, tx);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        script.stop();
+        let event = timeout(Duration::from_secs(5), rx.recv()).await.unwrap();
+        assert!(
+            matches!(event, Some(ScriptEvent::Finished(Err(e))) if e.contains("script stopped"))
+        );
+    }
+}
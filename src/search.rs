@@ -0,0 +1,105 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Search {
+    pub query: String,
+    pub regex: bool,
+    pub case_sensitive: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub line: usize,
+    pub range: Range<usize>,
+}
+
+impl Search {
+    pub fn is_active(&self) -> bool {
+        !self.query.is_empty()
+    }
+
+    fn compile(&self) -> Result<Regex, regex::Error> {
+        let pattern = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+    }
+
+    /// Finds every non-empty match in the given lines, in order.
+    pub fn find(&self, lines: &[&str]) -> Result<Vec<Match>, regex::Error> {
+        if !self.is_active() {
+            return Ok(Vec::new());
+        }
+        let re = self.compile()?;
+        Ok(lines
+            .iter()
+            .enumerate()
+            .flat_map(|(line, text)| {
+                re.find_iter(text)
+                    .filter(|m| !m.is_empty())
+                    .map(move |m| Match {
+                        line,
+                        range: m.range(),
+                    })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(query: &str) -> Search {
+        Search {
+            query: query.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn finds_every_match_in_order() {
+        let matches = search("ab").find(&["xAbab", "none", "ab"]).unwrap();
+        let found = matches
+            .iter()
+            .map(|m| (m.line, m.range.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(found, [(0, 1..3), (0, 3..5), (2, 0..2)]);
+    }
+
+    #[test]
+    fn case_sensitive() {
+        let s = Search {
+            case_sensitive: true,
+            ..search("ab")
+        };
+        assert_eq!(s.find(&["xAbab"]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn regex_skips_empty_matches() {
+        let s = Search {
+            regex: true,
+            ..search("a*")
+        };
+        let matches = s.find(&["baa"]).unwrap();
+        assert_eq!(
+            matches,
+            [Match {
+                line: 0,
+                range: 1..3
+            }]
+        );
+    }
+
+    #[test]
+    fn inactive_finds_nothing() {
+        assert!(Search::default().find(&["text"]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,66 @@
+//! Settings persisted as YAML in the user's config directory.
+
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use dirs::config_dir;
+use log::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+use serial_monitor::{
+    highlight::{HighlightRules, Highlighter},
+    recorder::RecordingSettings,
+};
+
+const APP_DIR: &str = "serial-monitor-gui";
+const HIGHLIGHT: &str = "highlight";
+const RECORDING: &str = "recording";
+
+fn path(name: &str) -> Option<PathBuf> {
+    Some(config_dir()?.join(APP_DIR).join(format!("{name}.yaml")))
+}
+
+/// Loads the settings stored under `name`, falling back to `T::default()` if
+/// they are missing or unreadable.
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+    let Some(path) = path(name) else {
+        return T::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(s) => serde_yaml::from_str(&s).unwrap_or_else(|e| {
+            warn!("Ignoring malformed settings {:?}: {:?}", path, e);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    let path = path(name).context("No config directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, serde_yaml::to_string(value)?)
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+pub fn load_highlighter() -> Highlighter {
+    Highlighter::new(load::<HighlightRules>(HIGHLIGHT).0)
+}
+
+pub fn save_highlighter(highlighter: &Highlighter) -> Result<()> {
+    save(HIGHLIGHT, &HighlightRules(highlighter.rules().to_vec()))
+}
+
+pub fn load_recording() -> RecordingSettings {
+    let mut recording: RecordingSettings = load(RECORDING);
+    // The panel never sets a limit of 0, but in a hand-edited file it would
+    // rotate on every write.
+    recording.max_size_mb = recording.max_size_mb.filter(|&mb| mb > 0);
+    recording.max_minutes = recording.max_minutes.filter(|&min| min > 0);
+    recording
+}
+
+pub fn save_recording(recording: &RecordingSettings) -> Result<()> {
+    save(RECORDING, recording)
+}
@@ -0,0 +1,390 @@
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Local};
+use log::*;
+use regex::bytes::Regex;
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+
+use serial_monitor::handle::{self, Chunk, Handle, LineSettings, Writer};
+
+use crate::responder::{escape, unescape};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    A,
+    B,
+}
+
+impl Side {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Side::A => "A",
+            Side::B => "B",
+        }
+    }
+
+    pub fn other(&self) -> Side {
+        match self {
+            Side::A => Side::B,
+            Side::B => Side::A,
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Side::A => 0,
+            Side::B => 1,
+        }
+    }
+}
+
+/// The direction(s) a rule applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Route {
+    #[default]
+    Both,
+    AToB,
+    BToA,
+}
+
+impl Route {
+    pub const ALL: [Route; 3] = [Route::Both, Route::AToB, Route::BToA];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Route::Both => "A ⇄ B",
+            Route::AToB => "A → B",
+            Route::BToA => "B → A",
+        }
+    }
+
+    fn applies(&self, from: Side) -> bool {
+        match self {
+            Route::Both => true,
+            Route::AToB => from == Side::A,
+            Route::BToA => from == Side::B,
+        }
+    }
+}
+
+/// Rewrites what matches `pattern` in data passing through. Data is matched
+/// a read at a time, as it is forwarded, so a match split across two reads
+/// is missed. Empty matches are ignored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub route: Route,
+    pub pattern: String,
+    pub replacement: String,
+    pub enabled: bool,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            route: Route::default(),
+            pattern: String::new(),
+            replacement: String::new(),
+            enabled: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    rule: Rule,
+    regex: Option<Regex>,
+}
+
+impl Entry {
+    fn new(rule: Rule) -> Self {
+        let regex = Regex::new(&rule.pattern).ok();
+        Self { rule, regex }
+    }
+
+    fn is_valid(&self) -> bool {
+        self.regex.is_some()
+    }
+}
+
+/// The rewrite rules, shared with the forwarding tasks so that edits apply
+/// at once. Clones refer to the same rules.
+#[derive(Clone, Debug, Default)]
+pub struct Rules {
+    entries: Arc<RwLock<Vec<Entry>>>,
+}
+
+impl Rules {
+    pub fn count(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    /// The rule at `index` and whether its pattern is valid.
+    pub fn get(&self, index: usize) -> Option<(Rule, bool)> {
+        let entries = self.entries.read().unwrap();
+        entries.get(index).map(|e| (e.rule.clone(), e.is_valid()))
+    }
+
+    pub fn push(&mut self, rule: Rule) {
+        self.entries.write().unwrap().push(Entry::new(rule));
+    }
+
+    pub fn edit(&mut self, index: usize, f: impl FnOnce(&mut Rule)) {
+        if let Some(e) = self.entries.write().unwrap().get_mut(index) {
+            let mut rule = e.rule.clone();
+            f(&mut rule);
+            *e = Entry::new(rule);
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        let mut entries = self.entries.write().unwrap();
+        if index < entries.len() {
+            entries.remove(index);
+        }
+    }
+
+    /// Applies every enabled rule for data coming `from` a side, in order.
+    /// Returns the data to forward and the names of the rules that matched.
+    fn apply(&self, from: Side, data: &[u8]) -> (Vec<u8>, Vec<String>) {
+        let mut data = data.to_vec();
+        let mut applied = Vec::new();
+        for e in self.entries.read().unwrap().iter() {
+            let Some(regex) = &e.regex else {
+                continue;
+            };
+            if !e.rule.enabled || !e.rule.route.applies(from) {
+                continue;
+            }
+            if let Some(replaced) = replace(regex, &data, &unescape(&e.rule.replacement)) {
+                data = replaced;
+                applied.push(e.rule.name.clone());
+            }
+        }
+        (data, applied)
+    }
+}
+
+/// Replaces every non-empty match of `regex`, or returns `None` if there is
+/// none.
+fn replace(regex: &Regex, data: &[u8], replacement: &[u8]) -> Option<Vec<u8>> {
+    let mut res = Vec::new();
+    let mut last = None;
+    for caps in regex.captures_iter(data) {
+        let m = caps.get(0)?;
+        if m.is_empty() {
+            continue;
+        }
+        res.extend_from_slice(&data[last.unwrap_or(0)..m.start()]);
+        caps.expand(replacement, &mut res);
+        last = Some(m.end());
+    }
+    res.extend_from_slice(&data[last?..]);
+    Some(res)
+}
+
+pub enum Request {
+    Start {
+        a: String,
+        b: String,
+        baud_rate: u32,
+    },
+    Stop,
+    /// Sends text, with escapes resolved, to one side.
+    Inject(Side, String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SnifferStatus {
+    pub running: bool,
+    pub error: Option<String>,
+}
+
+/// Data that went from one side to the other, as it was read.
+pub struct Forwarded {
+    pub time: DateTime<Local>,
+    pub from: Side,
+    pub received: Vec<u8>,
+    /// What was written to the other side, if rules changed it.
+    pub sent: Option<Vec<u8>>,
+    pub rules: Vec<String>,
+}
+
+impl Forwarded {
+    /// Formats the frame as console lines, with control characters escaped
+    /// so that both sides line up.
+    pub fn lines(&self) -> String {
+        let time = self.time.format("%H:%M:%S%.3f");
+        let route = format!("{}→{}", self.from.name(), self.from.other().name());
+        let text = escape(&self.received);
+        let mut res = format!("[{time}] {route} {text}\n");
+        if let Some(sent) = &self.sent {
+            let sent = escape(sent);
+            res.push_str(&format!(
+                "[{time}] {route} [{}] {sent}\n",
+                self.rules.join(", ")
+            ));
+        }
+        res
+    }
+}
+
+/// Two ports with the PC in between, forwarding everything each one
+/// receives to the other. Each side has its own task that passes data on as
+/// soon as it is read, so nothing waits for line ends or the UI.
+pub struct Sniffer {
+    ports: [(String, Handle); 2],
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Sniffer {
+    /// Opens both ports and starts forwarding with `rules`. What went
+    /// through is reported on `forwarded`.
+    pub fn open(
+        a: &str,
+        b: &str,
+        baud_rate: u32,
+        rules: Rules,
+        forwarded: UnboundedSender<Forwarded>,
+    ) -> handle::Result<Self> {
+        let settings = LineSettings::default();
+        let handle_a = Handle::open_raw(a, baud_rate, settings)?;
+        let handle_b = Handle::open_raw(b, baud_rate, settings)?;
+        let ports = [(a.to_string(), handle_a), (b.to_string(), handle_b)];
+        let mut tasks = Vec::new();
+        for from in [Side::A, Side::B] {
+            let (tx, rx) = unbounded_channel();
+            ports[from.index()].1.subscribe_raw(tx)?;
+            let to = ports[from.other().index()].1.writer();
+            tasks.push(tokio::spawn(forward(
+                from,
+                rx,
+                to,
+                rules.clone(),
+                forwarded.clone(),
+            )));
+        }
+        Ok(Self { ports, tasks })
+    }
+
+    pub fn name(&self, side: Side) -> &str {
+        &self.ports[side.index()].0
+    }
+
+    pub fn write(&self, side: Side, data: &[u8]) -> handle::Result<()> {
+        self.ports[side.index()].1.write_bytes(data)
+    }
+
+    /// The name of a port that was lost, if any.
+    pub fn lost(&self) -> Option<&str> {
+        self.ports
+            .iter()
+            .find(|(_, h)| !h.is_connected())
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+impl Drop for Sniffer {
+    fn drop(&mut self) {
+        self.tasks.iter().for_each(|t| t.abort());
+    }
+}
+
+async fn forward(
+    from: Side,
+    mut received: UnboundedReceiver<Chunk>,
+    to: Writer,
+    rules: Rules,
+    forwarded: UnboundedSender<Forwarded>,
+) {
+    while let Some(chunk) = received.recv().await {
+        let (sent, applied) = rules.apply(from, &chunk.data);
+        if let Err(e) = to.write_bytes(&sent) {
+            warn!("Failed to forward to {}: {:?}", from.other().name(), e);
+            return;
+        }
+        let _ = forwarded.send(Forwarded {
+            time: chunk.time,
+            from,
+            sent: (sent != chunk.data).then_some(sent),
+            received: chunk.data,
+            rules: applied,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, route: Route, pattern: &str, replacement: &str) -> Rule {
+        Rule {
+            name: name.to_string(),
+            route,
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn routes() {
+        assert!(Route::Both.applies(Side::A) && Route::Both.applies(Side::B));
+        assert!(Route::AToB.applies(Side::A) && !Route::AToB.applies(Side::B));
+        assert!(Route::BToA.applies(Side::B) && !Route::BToA.applies(Side::A));
+    }
+
+    #[test]
+    fn rules_apply_in_order() {
+        let mut rules = Rules::default();
+        rules.push(rule("first", Route::Both, "a", "b"));
+        rules.push(rule("second", Route::Both, "b+", "[$0]"));
+        let (sent, applied) = rules.apply(Side::A, b"abc");
+        assert_eq!(sent, b"[bb]c");
+        assert_eq!(applied, ["first", "second"]);
+    }
+
+    #[test]
+    fn rules_follow_their_route() {
+        let mut rules = Rules::default();
+        rules.push(rule("to b", Route::AToB, "x", "y"));
+        assert_eq!(rules.apply(Side::A, b"x").0, b"y");
+        let (sent, applied) = rules.apply(Side::B, b"x");
+        assert_eq!(sent, b"x");
+        assert!(applied.is_empty());
+    }
+
+    #[test]
+    fn replacement_escapes_are_bytes() {
+        let mut rules = Rules::default();
+        rules.push(rule("", Route::Both, r"(?-u)\xff", r"\x00\r"));
+        assert_eq!(rules.apply(Side::A, &[1, 0xff, 2]).0, [1, 0, b'\r', 2]);
+    }
+
+    #[test]
+    fn empty_matches_are_ignored() {
+        let mut rules = Rules::default();
+        rules.push(rule("end", Route::Both, "$", "\\n"));
+        rules.push(rule("empty", Route::Both, "", "!"));
+        rules.push(rule("star", Route::Both, "z*", "-"));
+        let (sent, applied) = rules.apply(Side::A, b"azzb");
+        assert_eq!(sent, b"a-b");
+        assert_eq!(applied, ["star"]);
+    }
+
+    #[test]
+    fn disabled_and_invalid_rules_are_skipped() {
+        let mut rules = Rules::default();
+        rules.push(Rule {
+            enabled: false,
+            ..rule("off", Route::Both, "a", "b")
+        });
+        rules.push(rule("bad", Route::Both, "(", "b"));
+        assert_eq!(rules.get(1).map(|(_, valid)| valid), Some(false));
+        assert_eq!(rules.apply(Side::A, b"a"), (b"a".to_vec(), Vec::new()));
+    }
+}
@@ -0,0 +1,59 @@
+//! Splitting a line into pieces of uniform ANSI and highlight style.
+
+use std::ops::Range;
+
+use crate::{
+    ansi::{Line, Style},
+    highlight::Highlighter,
+};
+
+/// A run of a rendered line sharing the same ANSI style, highlight rule and
+/// search mark. `mark` is `Some(true)` for the current search hit.
+pub struct Piece<'a> {
+    pub text: &'a str,
+    pub style: Style,
+    pub rule: Option<usize>,
+    pub mark: Option<bool>,
+}
+
+impl Piece<'_> {
+    pub fn css(&self, highlighter: &Highlighter) -> String {
+        let mut css = self.style.css();
+        if let Some(rule) = self.rule.and_then(|r| highlighter.rules().get(r)) {
+            css.push_str(&rule.style());
+        }
+        css
+    }
+}
+
+pub fn pieces<'a>(
+    line: &'a Line,
+    highlighter: &Highlighter,
+    marks: &[(Range<usize>, bool)],
+) -> Vec<Piece<'a>> {
+    let text = line.text.as_str();
+    let styles = line.styles();
+    let rules = highlighter.owners(text);
+    let mut mark = vec![None; text.len()];
+    for (range, current) in marks {
+        mark[range.clone()]
+            .iter_mut()
+            .for_each(|m| *m = Some(*current));
+    }
+
+    let key = |i: usize| (styles[i], rules[i], mark[i]);
+    let mut res = Vec::new();
+    let mut start = 0;
+    for end in 1..=text.len() {
+        if end == text.len() || (text.is_char_boundary(end) && key(end) != key(start)) {
+            res.push(Piece {
+                text: &text[start..end],
+                style: styles[start],
+                rule: rules[start],
+                mark: mark[start],
+            });
+            start = end;
+        }
+    }
+    res
+}
@@ -0,0 +1,284 @@
+use std::io;
+
+use log::*;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc::UnboundedSender,
+    },
+};
+
+use serial_monitor::record::{Direction, Record};
+
+use crate::bridge::Event;
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const BRK: u8 = 243;
+/// Interrupt Process, what clients send for ^C in some modes.
+const IP: u8 = 244;
+
+const ECHO: u8 = 1;
+const SGA: u8 = 3;
+
+const READ_BUF_SIZE: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Data,
+    /// After a CR, which clients may follow with a NUL.
+    Cr,
+    Iac,
+    Option(u8),
+    Sub,
+    SubIac,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Input {
+    Data(Vec<u8>),
+    Break,
+}
+
+/// The server side of a telnet session. We offer to echo and to suppress
+/// go-ahead, which puts stock clients into character mode without local
+/// echo; the echo itself comes from the device.
+pub struct Telnet {
+    state: State,
+    echo: bool,
+    sga: bool,
+}
+
+impl Telnet {
+    /// Returns the session and the negotiation to send first.
+    pub fn start() -> (Self, Vec<u8>) {
+        let telnet = Self {
+            state: State::Data,
+            echo: true,
+            sga: true,
+        };
+        (telnet, vec![IAC, WILL, ECHO, IAC, WILL, SGA, IAC, DO, SGA])
+    }
+
+    /// Splits what the client sent into data for the port and commands.
+    /// Answers to the client's negotiation are appended to `reply`.
+    pub fn receive(&mut self, bytes: &[u8], reply: &mut Vec<u8>) -> Vec<Input> {
+        let mut res = Vec::new();
+        let mut data = Vec::new();
+        for &b in bytes {
+            self.state = match (self.state, b) {
+                (State::Data | State::Cr, IAC) => State::Iac,
+                (State::Cr, 0) => State::Data,
+                (State::Data | State::Cr, b) => {
+                    data.push(b);
+                    if b == b'\r' {
+                        State::Cr
+                    } else {
+                        State::Data
+                    }
+                }
+                (State::Iac, IAC) => {
+                    data.push(IAC);
+                    State::Data
+                }
+                (State::Iac, BRK) => {
+                    if !data.is_empty() {
+                        res.push(Input::Data(std::mem::take(&mut data)));
+                    }
+                    res.push(Input::Break);
+                    State::Data
+                }
+                (State::Iac, IP) => {
+                    data.push(0x03);
+                    State::Data
+                }
+                (State::Iac, verb @ (DO | DONT | WILL | WONT)) => State::Option(verb),
+                (State::Iac, SB) => State::Sub,
+                // Other commands (NOP, AYT, ...) don't apply to a serial line.
+                (State::Iac, _) => State::Data,
+                (State::Option(verb), option) => {
+                    self.negotiate(verb, option, reply);
+                    State::Data
+                }
+                (State::Sub, IAC) => State::SubIac,
+                (State::Sub, _) => State::Sub,
+                (State::SubIac, SE) => State::Data,
+                (State::SubIac, _) => State::Sub,
+            };
+        }
+        if !data.is_empty() {
+            res.push(Input::Data(data));
+        }
+        res
+    }
+
+    /// Agrees to echo and go-ahead suppression and refuses everything else.
+    /// Only changes are answered, so that the two sides can't loop.
+    fn negotiate(&mut self, verb: u8, option: u8, reply: &mut Vec<u8>) {
+        let ours = match option {
+            ECHO => Some(&mut self.echo),
+            SGA => Some(&mut self.sga),
+            _ => None,
+        };
+        match (verb, ours) {
+            (DO, Some(enabled)) if !*enabled => {
+                *enabled = true;
+                reply.extend([IAC, WILL, option]);
+            }
+            (DONT, Some(enabled)) if *enabled => {
+                *enabled = false;
+                reply.extend([IAC, WONT, option]);
+            }
+            (DO, None) => reply.extend([IAC, WONT, option]),
+            // Our own DO SGA was sent up front, so only refuse the rest.
+            (WILL, _) if option != SGA => reply.extend([IAC, DONT, option]),
+            _ => (),
+        }
+    }
+}
+
+/// Doubles IAC bytes so that data isn't taken for commands.
+pub fn escape(data: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(data.len());
+    for &b in data {
+        res.push(b);
+        if b == IAC {
+            res.push(IAC);
+        }
+    }
+    res
+}
+
+/// Like the raw bridge client, but speaking telnet.
+pub async fn client(
+    id: usize,
+    mut stream: TcpStream,
+    mut data: broadcast::Receiver<Record>,
+    events: &UnboundedSender<Event>,
+) -> io::Result<()> {
+    let (mut telnet, greeting) = Telnet::start();
+    stream.write_all(&greeting).await?;
+    let mut buf = [0; READ_BUF_SIZE];
+    loop {
+        tokio::select! {
+            n = stream.read(&mut buf) => {
+                let n = n?;
+                if n == 0 {
+                    return Ok(());
+                }
+                let mut reply = Vec::new();
+                for input in telnet.receive(&buf[..n], &mut reply) {
+                    let _ = events.send(match input {
+                        Input::Data(bytes) => Event::Data(id, bytes),
+                        Input::Break => Event::Break(id),
+                    });
+                }
+                if !reply.is_empty() {
+                    stream.write_all(&reply).await?;
+                }
+            }
+            record = data.recv() => match record {
+                Ok(record) if record.direction == Direction::Rx => {
+                    stream.write_all(&escape(&record.data)).await?
+                }
+                Ok(_) => (),
+                Err(RecvError::Lagged(n)) => warn!("Telnet client {id} missed {n} records"),
+                Err(RecvError::Closed) => return Ok(()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receive(bytes: &[u8]) -> (Vec<Input>, Vec<u8>) {
+        let (mut telnet, _) = Telnet::start();
+        let mut reply = Vec::new();
+        let inputs = telnet.receive(bytes, &mut reply);
+        (inputs, reply)
+    }
+
+    #[test]
+    fn escape_doubles_iac() {
+        assert_eq!(escape(&[1, IAC, 2]), [1, IAC, IAC, 2]);
+        assert_eq!(escape(b"plain"), b"plain");
+    }
+
+    #[test]
+    fn receive_unescapes_iac() {
+        let (inputs, reply) = receive(&[b'a', IAC, IAC, b'b']);
+        assert_eq!(inputs, [Input::Data(vec![b'a', IAC, b'b'])]);
+        assert!(reply.is_empty());
+    }
+
+    #[test]
+    fn receive_drops_nul_after_cr() {
+        let (inputs, _) = receive(b"a\r\0b\r\n");
+        assert_eq!(inputs, [Input::Data(b"a\rb\r\n".to_vec())]);
+    }
+
+    #[test]
+    fn cr_nul_split_across_reads() {
+        let (mut telnet, _) = Telnet::start();
+        let mut reply = Vec::new();
+        assert_eq!(
+            telnet.receive(b"a\r", &mut reply),
+            [Input::Data(b"a\r".to_vec())]
+        );
+        assert_eq!(
+            telnet.receive(b"\0b", &mut reply),
+            [Input::Data(b"b".to_vec())]
+        );
+    }
+
+    #[test]
+    fn break_splits_data() {
+        let (inputs, _) = receive(&[b'a', b'b', IAC, BRK, b'c']);
+        assert_eq!(
+            inputs,
+            [
+                Input::Data(b"ab".to_vec()),
+                Input::Break,
+                Input::Data(b"c".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn interrupt_becomes_ctrl_c() {
+        let (inputs, _) = receive(&[IAC, IP]);
+        assert_eq!(inputs, [Input::Data(vec![0x03])]);
+    }
+
+    #[test]
+    fn unknown_options_are_refused() {
+        // Terminal type and window size.
+        let (inputs, reply) = receive(&[IAC, DO, 24, IAC, WILL, 31]);
+        assert!(inputs.is_empty());
+        assert_eq!(reply, [IAC, WONT, 24, IAC, DONT, 31]);
+    }
+
+    #[test]
+    fn only_changes_are_answered() {
+        let (_, reply) = receive(&[IAC, DO, ECHO, IAC, WILL, SGA]);
+        assert!(reply.is_empty());
+        let (_, reply) = receive(&[IAC, DONT, ECHO, IAC, DO, ECHO]);
+        assert_eq!(reply, [IAC, WONT, ECHO, IAC, WILL, ECHO]);
+    }
+
+    #[test]
+    fn subnegotiation_is_skipped() {
+        let (inputs, reply) = receive(&[IAC, SB, 24, 1, IAC, SE, b'x']);
+        assert_eq!(inputs, [Input::Data(b"x".to_vec())]);
+        assert!(reply.is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+use dioxus::html::input_data::keyboard_types::{Key, Modifiers};
+
+use serial_monitor::ansi::{Color, Style};
+
+pub const SCROLLBACK: usize = 10_000;
+pub const SIZES: [(u16, u16); 4] = [(24, 80), (30, 100), (40, 120), (43, 132)];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BackspaceKey {
+    /// ASCII DEL (0x7f), what xterm and most shells expect.
+    #[default]
+    Del,
+    /// ASCII BS (0x08, ^H), expected by some bootloaders.
+    Bs,
+}
+
+impl BackspaceKey {
+    fn code(&self) -> &'static str {
+        match self {
+            BackspaceKey::Del => "\x7f",
+            BackspaceKey::Bs => "\x08",
+        }
+    }
+}
+
+/// Translates a key press into the bytes an xterm would send for it.
+pub fn encode_key(
+    key: &Key,
+    modifiers: Modifiers,
+    application_cursor: bool,
+    backspace: BackspaceKey,
+) -> Option<String> {
+    if modifiers.contains(Modifiers::META) {
+        return None;
+    }
+    let cursor = |c: char| {
+        if application_cursor {
+            format!("\x1bO{c}")
+        } else {
+            format!("\x1b[{c}")
+        }
+    };
+    let seq = match key {
+        Key::Character(s) if modifiers.contains(Modifiers::CONTROL) => {
+            let mut chars = s.chars();
+            match (chars.next()?, chars.next()) {
+                (c, None) => control_char(c)?.to_string(),
+                _ => return None,
+            }
+        }
+        Key::Character(s) => s.clone(),
+        Key::Enter => "\r".to_string(),
+        Key::Tab if modifiers.contains(Modifiers::SHIFT) => "\x1b[Z".to_string(),
+        Key::Tab => "\t".to_string(),
+        Key::Backspace => backspace.code().to_string(),
+        Key::Escape => "\x1b".to_string(),
+        Key::ArrowUp => cursor('A'),
+        Key::ArrowDown => cursor('B'),
+        Key::ArrowRight => cursor('C'),
+        Key::ArrowLeft => cursor('D'),
+        Key::Home => cursor('H'),
+        Key::End => cursor('F'),
+        Key::Insert => "\x1b[2~".to_string(),
+        Key::Delete => "\x1b[3~".to_string(),
+        Key::PageUp => "\x1b[5~".to_string(),
+        Key::PageDown => "\x1b[6~".to_string(),
+        Key::F1 => "\x1bOP".to_string(),
+        Key::F2 => "\x1bOQ".to_string(),
+        Key::F3 => "\x1bOR".to_string(),
+        Key::F4 => "\x1bOS".to_string(),
+        Key::F5 => "\x1b[15~".to_string(),
+        Key::F6 => "\x1b[17~".to_string(),
+        Key::F7 => "\x1b[18~".to_string(),
+        Key::F8 => "\x1b[19~".to_string(),
+        Key::F9 => "\x1b[20~".to_string(),
+        Key::F10 => "\x1b[21~".to_string(),
+        Key::F11 => "\x1b[23~".to_string(),
+        Key::F12 => "\x1b[24~".to_string(),
+        _ => return None,
+    };
+    if modifiers.contains(Modifiers::ALT) {
+        Some(format!("\x1b{seq}"))
+    } else {
+        Some(seq)
+    }
+}
+
+/// Maps Ctrl+`c` to its C0 control character, e.g. Ctrl+C to ETX.
+pub fn control_char(c: char) -> Option<char> {
+    let code = match c.to_ascii_lowercase() {
+        c @ 'a'..='z' => c as u8 & 0x1f,
+        '@' | ' ' | '2' => 0x00,
+        '[' | '3' => 0x1b,
+        '\\' | '4' => 0x1c,
+        ']' | '5' => 0x1d,
+        '^' | '6' => 0x1e,
+        '_' | '-' | '7' => 0x1f,
+        '?' | '8' => 0x7f,
+        _ => return None,
+    };
+    Some(code as char)
+}
+
+fn color(c: vt100::Color) -> Option<Color> {
+    match c {
+        vt100::Color::Default => None,
+        vt100::Color::Idx(i) => Some(Color::Indexed(i)),
+        vt100::Color::Rgb(r, g, b) => Some(Color::Rgb(r, g, b)),
+    }
+}
+
+fn style(cell: &vt100::Cell) -> Style {
+    Style {
+        fg: color(cell.fgcolor()),
+        bg: color(cell.bgcolor()),
+        bold: cell.bold(),
+        italic: cell.italic(),
+        underline: cell.underline(),
+        inverse: cell.inverse(),
+        ..Default::default()
+    }
+}
+
+pub struct Run {
+    pub text: String,
+    pub style: Style,
+    pub cursor: bool,
+}
+
+/// Groups the visible cells of `screen` into runs of equal style, one list
+/// per row. The cell under the cursor always forms a run of its own.
+pub fn rows(screen: &vt100::Screen) -> Vec<Vec<Run>> {
+    let (rows, cols) = screen.size();
+    let cursor =
+        (!screen.hide_cursor() && screen.scrollback() == 0).then(|| screen.cursor_position());
+    (0..rows)
+        .map(|row| {
+            let mut runs: Vec<Run> = Vec::new();
+            for col in 0..cols {
+                let Some(cell) = screen.cell(row, col) else {
+                    continue;
+                };
+                if cell.is_wide_continuation() {
+                    continue;
+                }
+                let text = if cell.has_contents() {
+                    cell.contents()
+                } else {
+                    " ".to_string()
+                };
+                let style = style(cell);
+                let is_cursor = cursor == Some((row, col));
+                match runs.last_mut() {
+                    Some(r) if !r.cursor && !is_cursor && r.style == style => {
+                        r.text.push_str(&text)
+                    }
+                    _ => runs.push(Run {
+                        text,
+                        style,
+                        cursor: is_cursor,
+                    }),
+                }
+            }
+            runs
+        })
+        .collect()
+}
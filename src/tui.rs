@@ -0,0 +1,501 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Stdout},
+    time::Instant,
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    Frame, Terminal,
+};
+use tokio::time::{interval, Duration};
+
+use serial_monitor::{
+    ansi::{self, AnsiMode},
+    connection::Connection,
+    export::{self, Format},
+    highlight::Highlighter,
+    ports::{self, PortInfo},
+    recorder::RecordingSettings,
+};
+
+use crate::{cli::TuiArgs, settings, READ_FREQ, SCAN_FREQ};
+
+const HELP: &str =
+    "F2 port · F3 baud · F4 download · F5 format · PgUp/PgDn scroll · ^L clear · ^Q quit";
+
+/// Restores the terminal however the UI exits, panics included.
+struct Screen(Terminal<CrosstermBackend<Stdout>>);
+
+impl Screen {
+    fn new() -> io::Result<Self> {
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(Self(Terminal::new(CrosstermBackend::new(io::stdout()))?))
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+#[derive(PartialEq, Eq)]
+enum Focus {
+    Input,
+    Ports,
+    Baud,
+}
+
+struct Tui {
+    connection: Connection,
+    available_ports: Vec<PortInfo>,
+    /// The port to connect to. Like the GUI, opening is retried until the
+    /// port appears.
+    port: Option<String>,
+    user_buffer: Console,
+    port_buffer: Console,
+    input: String,
+    baud: String,
+    focus: Focus,
+    port_list: ListState,
+    format: Format,
+    highlighter: Highlighter,
+    /// Lines scrolled back from the bottom of the received pane.
+    scroll: usize,
+    status: String,
+    quit: bool,
+}
+
+pub async fn run(args: TuiArgs) -> io::Result<()> {
+    let mut connection = Connection::new(args.baud);
+    if let Err(e) = connection.set_line_settings(args.line.settings()) {
+        log::error!("{:?}", e);
+    }
+    let mut tui = Tui {
+        connection,
+        available_ports: ports::get_available_usb(),
+        port: args.port,
+        user_buffer: Console::default(),
+        port_buffer: Console::default(),
+        input: String::new(),
+        baud: args.baud.to_string(),
+        focus: Focus::Input,
+        port_list: ListState::default(),
+        format: Format::default(),
+        highlighter: settings::load_highlighter(),
+        scroll: 0,
+        status: String::new(),
+        quit: false,
+    };
+    let mut screen = Screen::new()?;
+    let mut interval = interval(READ_FREQ);
+    let mut last_scan = Instant::now();
+    tui.connect();
+    while !tui.quit {
+        interval.tick().await;
+        if last_scan.elapsed() >= SCAN_FREQ {
+            last_scan = Instant::now();
+            tui.available_ports = ports::get_available_usb();
+            tui.connect();
+        }
+        tui.read();
+        while event::poll(Duration::ZERO)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Release {
+                    tui.key(key);
+                }
+            }
+        }
+        screen.0.draw(|f| tui.draw(f))?;
+    }
+    Ok(())
+}
+
+impl Tui {
+    fn connect(&mut self) {
+        let Some(port) = &self.port else {
+            return;
+        };
+        // A port that was unplugged is opened again once it comes back.
+        if self.connection.is_connected() {
+            return;
+        }
+        match self.connection.open(port) {
+            Ok(_) => self.status = format!("Connected to {port}"),
+            Err(e) => self.status = format!("Waiting for {port}: {e}"),
+        }
+    }
+
+    fn read(&mut self) {
+        if !self.connection.is_connected() {
+            return;
+        }
+        // Take every frame that arrived since the last tick.
+        loop {
+            match self.connection.read() {
+                Ok(data) if !data.is_empty() => self.port_buffer.push(&data),
+                Ok(_) => break,
+                Err(e) => {
+                    self.status = format!("Read failed: {e}");
+                    break;
+                }
+            }
+        }
+    }
+
+    fn send(&mut self) {
+        let text = format!("{}\n", std::mem::take(&mut self.input));
+        match self.connection.write(&text) {
+            Ok(_) => self.user_buffer.push(&text),
+            Err(e) => self.status = format!("Send failed: {e}"),
+        }
+    }
+
+    fn key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match (key.code, ctrl) {
+            (KeyCode::Char('q') | KeyCode::Char('c'), true) => self.quit = true,
+            (KeyCode::Char('l'), true) => {
+                self.user_buffer.clear();
+                self.port_buffer.clear();
+                self.scroll = 0;
+            }
+            (KeyCode::F(2), _) => {
+                let current = self
+                    .available_ports
+                    .iter()
+                    .position(|p| Some(p.name()) == self.port.as_deref())
+                    .map_or(0, |i| i + 1);
+                self.port_list.select(Some(current));
+                self.focus = Focus::Ports;
+            }
+            (KeyCode::F(3), _) => self.focus = Focus::Baud,
+            (KeyCode::F(4), _) => self.download(),
+            (KeyCode::F(5), _) => {
+                let formats = formats();
+                let i = formats.iter().position(|f| *f == self.format).unwrap_or(0);
+                self.format = formats[(i + 1) % formats.len()];
+            }
+            (KeyCode::PageUp, _) => {
+                self.scroll = (self.scroll + 10).min(self.port_buffer.len());
+            }
+            (KeyCode::PageDown, _) => self.scroll = self.scroll.saturating_sub(10),
+            _ => match self.focus {
+                Focus::Input => self.input_key(key),
+                Focus::Ports => self.ports_key(key),
+                Focus::Baud => self.baud_key(key),
+            },
+        }
+    }
+
+    fn input_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter if !self.input.is_empty() => self.send(),
+            _ => (),
+        }
+    }
+
+    fn ports_key(&mut self, key: KeyEvent) {
+        // The first entry disconnects, like "Select port" in the GUI.
+        let count = self.available_ports.len() + 1;
+        let selected = self.port_list.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Up => self.port_list.select(Some((selected + count - 1) % count)),
+            KeyCode::Down => self.port_list.select(Some((selected + 1) % count)),
+            KeyCode::Enter => {
+                self.connection.close();
+                self.port = selected
+                    .checked_sub(1)
+                    .and_then(|i| self.available_ports.get(i))
+                    .map(|p| p.name().to_string());
+                self.status = String::new();
+                self.connect();
+                self.focus = Focus::Input;
+            }
+            KeyCode::Esc => self.focus = Focus::Input,
+            _ => (),
+        }
+    }
+
+    fn baud_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char(c) if c.is_ascii_digit() => self.baud.push(c),
+            KeyCode::Backspace => {
+                self.baud.pop();
+            }
+            KeyCode::Enter => {
+                match self.baud.parse::<u32>() {
+                    Ok(x) => match self.connection.set_baud_rate(x) {
+                        Ok(_) => self.status = format!("Baud rate set to {x}"),
+                        Err(e) => self.status = format!("Failed to set baud rate to {x}: {e}"),
+                    },
+                    Err(_) => self.status = "Not a valid number".to_string(),
+                }
+                self.focus = Focus::Input;
+            }
+            KeyCode::Esc => {
+                self.baud = self.connection.get_baud_rate().to_string();
+                self.focus = Focus::Input;
+            }
+            _ => (),
+        }
+    }
+
+    /// Saves the session to the download directory in the chosen format.
+    fn download(&mut self) {
+        let settings = RecordingSettings {
+            format: self.format,
+            ..Default::default()
+        };
+        let Some(dir) = dirs::download_dir() else {
+            self.status = "No download directory".to_string();
+            return;
+        };
+        let path = dir.join(settings.file_name(self.connection.get_name(), 1));
+        let res = File::create(&path).and_then(|f| {
+            export::write(
+                self.format,
+                self.connection.records(),
+                self.highlighter.rules(),
+                &self.connection.metadata(),
+                BufWriter::new(f),
+            )
+        });
+        self.status = match res {
+            Ok(_) => format!("Saved {}", path.display()),
+            Err(e) => format!("Download failed: {e}"),
+        };
+    }
+
+    fn draw(&mut self, f: &mut Frame) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(3),
+                Constraint::Length(3),
+                Constraint::Length(1),
+            ])
+            .split(f.size());
+        let top = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Min(20),
+                Constraint::Length(16),
+                Constraint::Length(24),
+            ])
+            .split(rows[0]);
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(rows[1]);
+
+        let state = match (&self.port, self.connection.is_connected()) {
+            (None, _) => Span::styled("no port", Style::default().fg(Color::DarkGray)),
+            (Some(_), true) => Span::styled("● connected", Style::default().fg(Color::Green)),
+            (Some(_), false) => Span::styled("○ connecting", Style::default().fg(Color::Yellow)),
+        };
+        let port = Line::from(vec![
+            Span::raw(self.port.as_deref().unwrap_or("Select port (F2)")),
+            Span::raw("  "),
+            state,
+        ]);
+        f.render_widget(
+            Paragraph::new(port).block(block("Port", self.focus == Focus::Ports)),
+            top[0],
+        );
+        f.render_widget(
+            Paragraph::new(self.baud.as_str()).block(block("Baud Rate", self.focus == Focus::Baud)),
+            top[1],
+        );
+        f.render_widget(
+            Paragraph::new(self.format.name()).block(block("Download (F4/F5)", false)),
+            top[2],
+        );
+
+        let user = self.user_buffer.view(panes[0].height, 0);
+        f.render_widget(Paragraph::new(user).block(block("Sent", false)), panes[0]);
+        let shown = panes[1].height.saturating_sub(2) as usize;
+        self.scroll = self
+            .scroll
+            .min(self.port_buffer.len().saturating_sub(shown));
+        let title = match self.scroll {
+            0 => "Received".to_string(),
+            n => format!("Received (−{n})"),
+        };
+        let received = self.port_buffer.view(panes[1].height, self.scroll);
+        f.render_widget(
+            Paragraph::new(received).block(block(&title, false)),
+            panes[1],
+        );
+
+        f.render_widget(
+            Paragraph::new(self.input.as_str()).block(block("Send", self.focus == Focus::Input)),
+            rows[2],
+        );
+        match self.focus {
+            Focus::Input => f.set_cursor(
+                rows[2].x + 1 + self.input.chars().count() as u16,
+                rows[2].y + 1,
+            ),
+            Focus::Baud => f.set_cursor(top[1].x + 1 + self.baud.len() as u16, top[1].y + 1),
+            Focus::Ports => (),
+        }
+        let footer = if self.status.is_empty() {
+            Span::styled(HELP, Style::default().fg(Color::DarkGray))
+        } else {
+            Span::raw(self.status.as_str())
+        };
+        f.render_widget(Paragraph::new(footer), rows[3]);
+
+        if self.focus == Focus::Ports {
+            let items = std::iter::once(ListItem::new("None"))
+                .chain(self.available_ports.iter().map(|p| {
+                    ListItem::new(format!(
+                        "{}  {}  {}",
+                        p.name(),
+                        p.manufacturer(),
+                        p.product()
+                    ))
+                }))
+                .collect::<Vec<_>>();
+            let area = centered(f.size(), 60, items.len() as u16 + 2);
+            let list = List::new(items)
+                .block(block("Select port (Enter, Esc)", true))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut self.port_list);
+        }
+    }
+}
+
+/// The formats that can be saved from the session's records.
+fn formats() -> Vec<Format> {
+    Format::ALL
+        .into_iter()
+        .filter(|f| *f != Format::CsvColumns)
+        .collect()
+}
+
+fn block(title: &str, focused: bool) -> Block<'_> {
+    let style = if focused {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+    Block::default()
+        .borders(Borders::ALL)
+        .border_style(style)
+        .title(title)
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// Text shown in a pane, parsed for ANSI colors as it arrives so that
+/// drawing doesn't go over the whole history again.
+#[derive(Default)]
+struct Console {
+    lines: Vec<ansi::Line>,
+    /// What came after the last line end, parsed again on every draw.
+    tail: String,
+    /// The style in effect at the start of `tail`.
+    style: ansi::Style,
+}
+
+impl Console {
+    fn push(&mut self, data: &str) {
+        self.tail.push_str(data);
+        if let Some(end) = self.tail.rfind('\n') {
+            let rest = self.tail.split_off(end + 1);
+            let done = std::mem::replace(&mut self.tail, rest);
+            let lines = ansi::parse_with(&done, AnsiMode::Render, &mut self.style);
+            self.lines.extend(lines);
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn len(&self) -> usize {
+        self.lines.len() + usize::from(!self.tail.is_empty())
+    }
+
+    /// The last lines that fit in a pane `height` rows high, `scroll` lines
+    /// up from the bottom, in their ANSI colors.
+    fn view(&self, height: u16, scroll: usize) -> Vec<Line<'static>> {
+        let tail = ansi::parse_with(&self.tail, AnsiMode::Render, &mut self.style.clone());
+        let rows = height.saturating_sub(2) as usize;
+        let end = self.len().saturating_sub(scroll);
+        self.lines
+            .iter()
+            .chain(&tail)
+            .take(end)
+            .skip(end.saturating_sub(rows))
+            .map(styled_line)
+            .collect()
+    }
+}
+
+fn styled_line(line: &ansi::Line) -> Line<'static> {
+    let styles = line.runs.iter().map(|(start, style)| (*start, *style));
+    let ends = line.runs.iter().skip(1).map(|(start, _)| *start);
+    let spans = styles
+        .zip(ends.chain(std::iter::once(line.text.len())))
+        .map(|((start, style), end)| {
+            Span::styled(line.text[start..end].to_string(), span_style(&style))
+        })
+        .collect::<Vec<_>>();
+    Line::from(spans)
+}
+
+fn span_style(style: &ansi::Style) -> Style {
+    let color = |c: ansi::Color| {
+        let (r, g, b) = c.rgb();
+        Color::Rgb(r, g, b)
+    };
+    let mut res = Style::default();
+    if let Some(fg) = style.fg {
+        res = res.fg(color(fg));
+    }
+    if let Some(bg) = style.bg {
+        res = res.bg(color(bg));
+    }
+    for (on, modifier) in [
+        (style.bold, Modifier::BOLD),
+        (style.dim, Modifier::DIM),
+        (style.italic, Modifier::ITALIC),
+        (style.underline, Modifier::UNDERLINED),
+        (style.inverse, Modifier::REVERSED),
+    ] {
+        if on {
+            res = res.add_modifier(modifier);
+        }
+    }
+    res
+}
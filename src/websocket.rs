@@ -0,0 +1,135 @@
+use std::io;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::{SinkExt, StreamExt};
+use log::*;
+use serde_json::{json, Value};
+use tokio::{
+    net::TcpStream,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc::UnboundedSender,
+    },
+};
+use tokio_tungstenite::tungstenite::{
+    self,
+    handshake::server::{ErrorResponse, Request, Response},
+    http::StatusCode,
+    Message,
+};
+
+use serial_monitor::record::{Direction, Record};
+
+use crate::bridge::Event;
+
+fn to_json(record: &Record) -> Value {
+    json!({
+        "timestamp": record.unix_time(),
+        "direction": match record.direction {
+            Direction::Rx => "rx",
+            Direction::Tx => "tx",
+        },
+        "port": record.port,
+        "base64": STANDARD.encode(&record.data),
+        "text": record.text(),
+    })
+}
+
+/// Reads a send command: `{"send": "text"}`, `{"base64": "..."}` or a
+/// binary message with the raw bytes.
+fn parse_command(msg: Message) -> Result<Option<Vec<u8>>, String> {
+    let text = match msg {
+        Message::Text(text) => text,
+        Message::Binary(bytes) => return Ok(Some(bytes)),
+        _ => return Ok(None),
+    };
+    let cmd: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    if let Some(s) = cmd.get("send").and_then(Value::as_str) {
+        Ok(Some(s.as_bytes().to_vec()))
+    } else if let Some(s) = cmd.get("base64").and_then(Value::as_str) {
+        STANDARD.decode(s).map(Some).map_err(|e| e.to_string())
+    } else {
+        Err("Expected {\"send\": string} or {\"base64\": string}".to_string())
+    }
+}
+
+/// Whether a browser's `Origin` is a page served from this machine. Any
+/// other page could otherwise connect and write to the port.
+fn local_origin(origin: &str) -> bool {
+    let Some(host) = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"))
+    else {
+        return false;
+    };
+    let host = match host.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+/// Lets through clients without an `Origin`, i.e. not browsers, and pages
+/// from this machine.
+// The error type is fixed by tungstenite's handshake callback.
+#[allow(clippy::result_large_err)]
+fn check_origin(req: &Request, res: Response) -> Result<Response, ErrorResponse> {
+    let origin = req
+        .headers()
+        .get("Origin")
+        .map(|o| o.to_str().unwrap_or_default());
+    match origin {
+        Some(origin) if !local_origin(origin) => {
+            warn!("Refused WebSocket client from {origin}");
+            let mut res = ErrorResponse::new(Some(format!("Origin {origin} not allowed")));
+            *res.status_mut() = StatusCode::FORBIDDEN;
+            Err(res)
+        }
+        _ => Ok(res),
+    }
+}
+
+/// Like the raw bridge client, but every record goes out as a JSON text
+/// message, sent data included.
+pub async fn client(
+    id: usize,
+    stream: TcpStream,
+    mut data: broadcast::Receiver<Record>,
+    events: &UnboundedSender<Event>,
+) -> io::Result<()> {
+    let to_io = |e: tungstenite::Error| io::Error::other(e);
+    let mut ws = tokio_tungstenite::accept_hdr_async(stream, check_origin)
+        .await
+        .map_err(to_io)?;
+    loop {
+        tokio::select! {
+            msg = ws.next() => {
+                let Some(msg) = msg else {
+                    return Ok(());
+                };
+                let msg = msg.map_err(to_io)?;
+                if msg.is_close() {
+                    return Ok(());
+                }
+                match parse_command(msg) {
+                    Ok(Some(bytes)) => {
+                        let _ = events.send(Event::Data(id, bytes));
+                    }
+                    Ok(None) => (),
+                    Err(e) => {
+                        let reply = json!({ "error": e }).to_string();
+                        ws.send(Message::Text(reply)).await.map_err(to_io)?;
+                    }
+                }
+            }
+            record = data.recv() => match record {
+                Ok(record) => {
+                    let msg = Message::Text(to_json(&record).to_string());
+                    ws.send(msg).await.map_err(to_io)?;
+                }
+                Err(RecvError::Lagged(n)) => warn!("WebSocket client {id} missed {n} records"),
+                Err(RecvError::Closed) => return Ok(()),
+            },
+        }
+    }
+}